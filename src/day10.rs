@@ -1,79 +1,413 @@
-use std::fs::read_to_string;
+use std::fmt;
+use std::fs::{read_to_string, File};
+use std::io::{self, ErrorKind, Read};
 use itertools::Itertools;
 
+struct BracketPair {
+    open: String,
+    close: String,
+    corrupted_score: i64,
+    complete_score: i64,
+}
+
+enum Token {
+    Open(usize),
+    Close(usize),
+}
+
+struct BracketSet {
+    pairs: Vec<BracketPair>,
+}
+
+impl BracketSet {
+    fn standard() -> BracketSet {
+        BracketSet::new("( ) 3 1\n[ ] 57 2\n{ } 1197 3\n< > 25137 4")
+    }
+
+    fn new(config: &str) -> BracketSet {
+        BracketSet {
+            pairs: config.lines()
+                .map(|line| line.split_whitespace().collect_vec())
+                .filter(|parts| !parts.is_empty())
+                .map(|parts| BracketPair {
+                    open: parts[0].to_string(),
+                    close: parts[1].to_string(),
+                    corrupted_score: parts[2].parse().unwrap(),
+                    complete_score: parts[3].parse().unwrap(),
+                })
+                .collect_vec()
+        }
+    }
+
+    fn token_at(&self, line: &[u8], pos: usize) -> Option<(Token, usize)> {
+        let rest = &line[pos..];
+        self.pairs.iter()
+            .enumerate()
+            .flat_map(|(idx, pair)| [(Token::Open(idx), pair.open.as_bytes()), (Token::Close(idx), pair.close.as_bytes())])
+            .filter(|(_, token)| rest.starts_with(token))
+            .max_by_key(|(_, token)| token.len())
+            .map(|(token, bytes)| (token, bytes.len()))
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Repair {
+    Delete,
+    Match(usize, usize),
+    Insert(usize, usize),
+}
+
+fn rebuild(choice: &Vec<Vec<Repair>>, brackets: &BracketSet, i: usize, j: usize) -> String {
+    if i >= j {
+        return "".to_string();
+    }
+    match choice[i][j] {
+        Repair::Delete => rebuild(choice, brackets, i + 1, j),
+        Repair::Match(k, pair) => format!("{}{}{}{}", brackets.pairs[pair].open, rebuild(choice, brackets, i + 1, k),
+                                          brackets.pairs[pair].close, rebuild(choice, brackets, k + 1, j)),
+        Repair::Insert(k, pair) => format!("{}{}{}{}", brackets.pairs[pair].open, rebuild(choice, brackets, i + 1, k),
+                                           brackets.pairs[pair].close, rebuild(choice, brackets, k, j)),
+    }
+}
+
+struct Diagnostic<'a> {
+    file: &'a str,
+    line_number: usize,
+    line: &'a Line,
+    corruption: Option<usize>,
+    found: String,
+    expected: Option<String>,
+    opener: Option<(String, usize)>,
+    repaired: String,
+}
+
+impl<'a> Diagnostic<'a> {
+    fn new(file: &'a str, line_number: usize, line: &'a Line, brackets: &BracketSet) -> Diagnostic<'a> {
+        let (corruption, stack) = line.scan(brackets);
+        let found = corruption
+            .map(|index| match brackets.token_at(line.line.as_bytes(), index) {
+                Some((Token::Open(pair), _)) => brackets.pairs[pair].open.clone(),
+                Some((Token::Close(pair), _)) => brackets.pairs[pair].close.clone(),
+                None => line.line[index..].chars().next().unwrap().to_string(),
+            })
+            .unwrap_or_default();
+        Diagnostic {
+            file,
+            line_number,
+            line,
+            corruption,
+            found,
+            expected: stack.last().map(|&(pair, _)| brackets.pairs[pair].close.clone()),
+            opener: stack.last().map(|&(pair, position)| (brackets.pairs[pair].open.clone(), position)),
+            repaired: line.repair(brackets),
+        }
+    }
+}
+
+impl<'a> fmt::Display for Diagnostic<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.corruption {
+            Some(column) => {
+                match &self.expected {
+                    Some(expected) => writeln!(f, "{}:{}:{}: error: expected `{}`, found `{}`",
+                                               self.file, self.line_number, column + 1, expected, self.found)?,
+                    None => writeln!(f, "{}:{}:{}: error: unexpected `{}`",
+                                     self.file, self.line_number, column + 1, self.found)?,
+                }
+                writeln!(f, "  {}", self.line.line)?;
+                writeln!(f, "  {}^", " ".repeat(column))?;
+                if let Some((opener, position)) = &self.opener {
+                    writeln!(f, "note: unmatched `{}` opened at column {}", opener, position + 1)?;
+                }
+            }
+            None => {
+                if self.line.line.len() == self.repaired.len() {
+                    return write!(f, "{}:{}: ok", self.file, self.line_number);
+                }
+                writeln!(f, "{}:{}:{}: warning: incomplete line, missing `{}`", self.file, self.line_number,
+                         self.line.line.len() + 1, &self.repaired[self.line.line.len()..])?;
+                writeln!(f, "  {}", self.line.line)?;
+                if let Some((opener, position)) = &self.opener {
+                    writeln!(f, "note: unmatched `{}` opened at column {}", opener, position + 1)?;
+                }
+            }
+        }
+        write!(f, "help: repaired: {}", self.repaired)
+    }
+}
+
 struct Line {
-    line: Vec<String>,
+    line: String,
 }
 
 impl Line {
     fn new(line: &str) -> Line {
         Line {
-            line: line.chars()
-                .map(|c| c.to_string())
-                .collect_vec()
+            line: line.to_string()
         }
     }
-    fn find_corruption(&self) -> (Option<usize>, Vec<String>) {
-        let mut stack: Vec<String> = vec![];
-        for (index, value) in self.line.iter().enumerate() {
-            if ["(", "[", "{", "<"].contains(&value.as_str()) {
-                stack.push(value.clone());
-            } else if stack.last().filter(|x| x.as_str() == match value.as_str() {
-                ")" => "(",
-                "]" => "[",
-                "}" => "{",
-                ">" => "<",
-                _ => ""
-            }).is_some() {
-                stack.pop();
-            } else {
-                return (Option::from(index), stack);
+    fn scan(&self, brackets: &BracketSet) -> (Option<usize>, Vec<(usize, usize)>) {
+        let line = self.line.as_bytes();
+        let mut stack: Vec<(usize, usize)> = vec![];
+        let mut index = 0;
+        while index < line.len() {
+            match brackets.token_at(line, index) {
+                Some((Token::Open(pair), len)) => {
+                    stack.push((pair, index));
+                    index += len;
+                }
+                Some((Token::Close(pair), len)) if stack.last().map(|&(open, _)| open) == Some(pair) => {
+                    stack.pop();
+                    index += len;
+                }
+                _ => return (Option::from(index), stack)
             }
         }
         return (Option::None, stack);
     }
 
-    fn is_corrupted(&self) -> bool {
-        self.find_corruption().0.is_some()
+    fn find_corruption(&self, brackets: &BracketSet) -> (Option<usize>, Vec<usize>) {
+        let (corruption, stack) = self.scan(brackets);
+        (corruption, stack.iter().map(|&(pair, _)| pair).collect_vec())
     }
 
-    fn get_corrupted_score(&self) -> i32 {
-        match self.line[self.find_corruption().0.unwrap()].as_str() {
-            ")" => 3,
-            "]" => 57,
-            "}" => 1197,
-            ">" => 25137,
-            _ => panic!()
+    fn tokens(&self, brackets: &BracketSet) -> Vec<Option<Token>> {
+        let line = self.line.as_bytes();
+        let mut tokens = vec![];
+        let mut index = 0;
+        while index < line.len() {
+            let (token, len) = match brackets.token_at(line, index) {
+                Some((token, len)) => (Some(token), len),
+                None => (None, 1)
+            };
+            tokens.push(token);
+            index += len;
         }
+        tokens
     }
-    fn get_complete_score(&self) -> i64 {
-        self.find_corruption().1
+
+    fn completion(&self, brackets: &BracketSet) -> String {
+        self.find_corruption(brackets).1
             .iter()
             .rev()
-            .map(|symbol| match symbol.as_str() {
-                "(" => 1,
-                "[" => 2,
-                "{" => 3,
-                "<" => 4,
-                _ => panic!()
-            })
+            .map(|&pair| brackets.pairs[pair].close.as_str())
+            .collect()
+    }
+
+    fn repair(&self, brackets: &BracketSet) -> String {
+        if !self.is_corrupted(brackets) {
+            return self.line.clone() + self.completion(brackets).as_str();
+        }
+        let tokens = self.tokens(brackets);
+        let n = tokens.len();
+        let is_open = |token: &Option<Token>, pair: usize| matches!(token, Some(Token::Open(p)) if *p == pair);
+        let is_close = |token: &Option<Token>, pair: usize| matches!(token, Some(Token::Close(p)) if *p == pair);
+        let pair_cost = |i: usize, k: usize| (0..brackets.pairs.len())
+            .map(|pair| ((!is_open(&tokens[i], pair)) as usize + (!is_close(&tokens[k], pair)) as usize, pair))
+            .min()
+            .unwrap();
+        let mut cost = vec![vec![0; n + 1]; n + 1];
+        let mut choice = vec![vec![Repair::Delete; n + 1]; n + 1];
+        for length in 1..n + 1 {
+            for i in 0..n + 1 - length {
+                let j = i + length;
+                let mut best = (1 + cost[i + 1][j], Repair::Delete);
+                for k in i + 1..j {
+                    let (pair_cost, pair) = pair_cost(i, k);
+                    best = best.min((pair_cost + cost[i + 1][k] + cost[k + 1][j], Repair::Match(k, pair)));
+                }
+                if let Some(Token::Open(pair)) = tokens[i] {
+                    for k in i + 1..j + 1 {
+                        best = best.min((1 + cost[i + 1][k] + cost[k][j], Repair::Insert(k, pair)));
+                    }
+                }
+                cost[i][j] = best.0;
+                choice[i][j] = best.1;
+            }
+        }
+        rebuild(&choice, brackets, 0, n)
+    }
+
+    fn is_corrupted(&self, brackets: &BracketSet) -> bool {
+        self.find_corruption(brackets).0.is_some()
+    }
+
+    fn get_corrupted_score(&self, brackets: &BracketSet) -> i64 {
+        match brackets.token_at(self.line.as_bytes(), self.find_corruption(brackets).0.unwrap()) {
+            Some((Token::Close(pair), _)) => brackets.pairs[pair].corrupted_score,
+            _ => 0
+        }
+    }
+    fn get_complete_score(&self, brackets: &BracketSet) -> i64 {
+        self.find_corruption(brackets).1
+            .iter()
+            .rev()
+            .map(|&pair| brackets.pairs[pair].complete_score)
             .fold(0, |acc, x| acc * 5 + x)
     }
 }
 
-fn part1(lines: &Vec<Line>) -> i32 {
+#[derive(Default, Debug)]
+struct StreamReport {
+    lines: usize,
+    valid: usize,
+    corrupted: usize,
+    incomplete: usize,
+    corrupted_score: i64,
+    first_error: Option<(usize, usize)>,
+    tokens: usize,
+    depth_sum: usize,
+    max_depth: usize,
+}
+
+impl StreamReport {
+    fn mean_depth(&self) -> f64 {
+        if self.tokens == 0 { 0.0 } else { self.depth_sum as f64 / self.tokens as f64 }
+    }
+}
+
+struct StreamValidator<'a> {
+    brackets: &'a BracketSet,
+    whole_file: bool,
+    longest_token: usize,
+    buffer: Vec<u8>,
+    stack: Vec<usize>,
+    corrupted: bool,
+    line: usize,
+    column: usize,
+    line_has_tokens: bool,
+    report: StreamReport,
+}
+
+impl<'a> StreamValidator<'a> {
+    fn new(brackets: &'a BracketSet, whole_file: bool) -> StreamValidator<'a> {
+        StreamValidator {
+            brackets,
+            whole_file,
+            longest_token: brackets.pairs.iter()
+                .flat_map(|pair| [pair.open.len(), pair.close.len()])
+                .max()
+                .unwrap_or(1),
+            buffer: vec![],
+            stack: vec![],
+            corrupted: false,
+            line: 1,
+            column: 1,
+            line_has_tokens: false,
+            report: StreamReport::default(),
+        }
+    }
+
+    fn feed(&mut self, chunk: &[u8]) {
+        self.buffer.extend_from_slice(chunk);
+        self.process(false);
+    }
+
+    fn finish(mut self) -> StreamReport {
+        self.process(true);
+        if self.whole_file || self.line_has_tokens {
+            self.end_sequence();
+        }
+        self.report
+    }
+
+    fn process(&mut self, at_end: bool) {
+        let mut index = 0;
+        while index < self.buffer.len() {
+            if !at_end && self.buffer.len() - index < self.longest_token {
+                break;
+            }
+            let byte = self.buffer[index];
+            if byte == b'\n' || byte == b'\r' {
+                if byte == b'\n' {
+                    if !self.whole_file {
+                        self.end_sequence();
+                    }
+                    self.line += 1;
+                    self.column = 0;
+                }
+                index += 1;
+                self.column += 1;
+                continue;
+            }
+            let len = match self.brackets.token_at(&self.buffer, index) {
+                _ if self.corrupted => 1,
+                Some((Token::Open(pair), len)) => {
+                    self.stack.push(pair);
+                    self.count_token();
+                    len
+                }
+                Some((Token::Close(pair), len)) if self.stack.last() == Some(&pair) => {
+                    self.count_token();
+                    self.stack.pop();
+                    len
+                }
+                found => {
+                    self.corrupted = true;
+                    self.line_has_tokens = true;
+                    self.report.first_error.get_or_insert((self.line, self.column));
+                    if let Some((Token::Close(pair), _)) = found {
+                        self.report.corrupted_score += self.brackets.pairs[pair].corrupted_score;
+                    }
+                    1
+                }
+            };
+            index += len;
+            self.column += len;
+        }
+        self.buffer.drain(..index);
+    }
+
+    fn count_token(&mut self) {
+        self.line_has_tokens = true;
+        self.report.tokens += 1;
+        self.report.depth_sum += self.stack.len();
+        self.report.max_depth = self.report.max_depth.max(self.stack.len());
+    }
+
+    fn end_sequence(&mut self) {
+        self.report.lines += 1;
+        if self.corrupted {
+            self.report.corrupted += 1;
+        } else if !self.stack.is_empty() {
+            self.report.incomplete += 1;
+        } else {
+            self.report.valid += 1;
+        }
+        self.stack.clear();
+        self.corrupted = false;
+        self.line_has_tokens = false;
+    }
+}
+
+fn validate_stream(mut reader: impl Read, brackets: &BracketSet, whole_file: bool) -> io::Result<StreamReport> {
+    let mut validator = StreamValidator::new(brackets, whole_file);
+    let mut chunk = [0u8; 8192];
+    loop {
+        let read = match reader.read(&mut chunk) {
+            Err(error) if error.kind() == ErrorKind::Interrupted => continue,
+            result => result?,
+        };
+        if read == 0 {
+            return Ok(validator.finish());
+        }
+        validator.feed(&chunk[..read]);
+    }
+}
+
+fn part1(lines: &Vec<Line>, brackets: &BracketSet) -> i64 {
     lines
         .iter()
-        .filter(|line| line.is_corrupted())
-        .map(|line| line.get_corrupted_score())
+        .filter(|line| line.is_corrupted(brackets))
+        .map(|line| line.get_corrupted_score(brackets))
         .sum()
 }
 
-fn part2(lines: &Vec<Line>) -> i64 {
+fn part2(lines: &Vec<Line>, brackets: &BracketSet) -> i64 {
     let completion_scores = lines
         .iter()
-        .filter(|line| !line.is_corrupted())
-        .map(|line| line.get_complete_score())
+        .filter(|line| !line.is_corrupted(brackets))
+        .map(|line| line.get_complete_score(brackets))
         .sorted()
         .collect_vec();
     completion_scores[completion_scores.len() / 2]
@@ -84,6 +418,25 @@ pub(crate) fn solve() {
         .lines()
         .map(|line| Line::new(line))
         .collect_vec();
-    println!("{}", part1(&data));
-    println!("{}", part2(&data));
-}
\ No newline at end of file
+    let brackets = match std::env::args().find_map(|arg| arg.strip_prefix("--brackets=").map(|path| path.to_string())) {
+        Some(path) => BracketSet::new(read_to_string(path).unwrap().as_str()),
+        None => BracketSet::standard()
+    };
+    println!("{}", part1(&data, &brackets));
+    println!("{}", part2(&data, &brackets));
+    if std::env::args().any(|arg| arg == "--diagnostics") {
+        for (index, line) in data.iter().enumerate() {
+            println!("{}", Diagnostic::new("10.txt", index + 1, line, &brackets));
+        }
+    }
+    if std::env::args().any(|arg| arg == "--stream") {
+        let whole_file = std::env::args().any(|arg| arg == "--whole-file");
+        match File::open("10.txt").and_then(|file| validate_stream(file, &brackets, whole_file)) {
+            Ok(report) => {
+                println!("{:?}", report);
+                println!("mean depth {:.2}", report.mean_depth());
+            }
+            Err(error) => eprintln!("error: {}", error),
+        }
+    }
+}
@@ -1,11 +1,66 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::fs::read_to_string;
 use std::hash::Hash;
 use itertools::Itertools;
 
+#[derive(Clone, Copy)]
+enum Neighbourhood {
+    Four,
+    Eight,
+    Hex,
+}
+
+#[derive(Clone, Copy)]
+enum Boundary {
+    Bounded,
+    Toroidal,
+}
+
+#[derive(Clone, Copy)]
+struct Rules {
+    threshold: i32,
+    neighbourhood: Neighbourhood,
+    boundary: Boundary,
+}
+
+impl Rules {
+    fn standard() -> Rules {
+        Rules { threshold: 9, neighbourhood: Neighbourhood::Eight, boundary: Boundary::Bounded }
+    }
+
+    fn from_args() -> Rules {
+        let args = std::env::args().collect_vec();
+        let standard = Rules::standard();
+        Rules {
+            threshold: args.iter()
+                .find_map(|arg| arg.strip_prefix("--threshold="))
+                .map_or(standard.threshold, |threshold| threshold.parse().unwrap()),
+            neighbourhood: match args.iter().find_map(|arg| arg.strip_prefix("--neighbourhood=")) {
+                Some("4") => Neighbourhood::Four,
+                Some("8") | None => Neighbourhood::Eight,
+                Some("hex") => Neighbourhood::Hex,
+                Some(other) => panic!("unknown neighbourhood: {}", other)
+            },
+            boundary: if args.iter().any(|arg| arg == "--toroidal") { Boundary::Toroidal } else { Boundary::Bounded },
+        }
+    }
+
+    fn offsets(&self, row: i32) -> Vec<(i32, i32)> {
+        match self.neighbourhood {
+            Neighbourhood::Four => vec![(-1, 0), (1, 0), (0, -1), (0, 1)],
+            Neighbourhood::Eight => vec![(-1, 0), (1, 0), (0, -1), (0, 1), (-1, -1), (1, -1), (-1, 1), (1, 1)],
+            Neighbourhood::Hex => {
+                let shift = row.rem_euclid(2);
+                vec![(-1, shift - 1), (-1, shift), (0, -1), (0, 1), (1, shift - 1), (1, shift)]
+            }
+        }
+    }
+}
+
 #[derive(Clone)]
 struct Board {
     board: Vec<Vec<i32>>,
+    rules: Rules,
 }
 
 impl Board {
@@ -21,63 +76,140 @@ impl Board {
         (*x >= 0) & (*x < self.height() as i32) & (*y >= 0) & (*y < self.width() as i32)
     }
 
+    fn size(&self) -> usize {
+        self.width() * self.height()
+    }
+
     fn neighbours(&self, x: usize, y: usize) -> Vec<(usize, usize)> {
         let x = x as i32;
         let y = y as i32;
-        let potential = [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1), (x - 1, y - 1), (x + 1, y - 1), (x - 1, y + 1), (x + 1, y + 1)];
+        let potential = self.rules.offsets(x)
+            .into_iter()
+            .map(|(dx, dy)| match self.rules.boundary {
+                Boundary::Bounded => (x + dx, y + dy),
+                Boundary::Toroidal => ((x + dx).rem_euclid(self.height() as i32), (y + dy).rem_euclid(self.width() as i32)),
+            })
+            .collect_vec();
         potential.iter()
             .filter(|(x, y)| self.is_valid_index(x, y))
+            .filter(|&&neighbour| neighbour != (x, y))
             .map(|(x, y)| (*x as usize, *y as usize))
+            .unique()
             .collect_vec()
     }
 
     fn step(&mut self) -> HashSet<(usize, usize)> {
+        self.step_with_stats().flashes
+    }
+
+    fn step_with_stats(&mut self) -> StepStats {
         let mut flashes: HashSet<(usize, usize)> = HashSet::new();
-        let mut triggers: Vec<(usize, usize)> = vec![];
+        let mut wave: Vec<(usize, usize)> = vec![];
         (0..self.height())
             .for_each(|i| (0..self.width())
                 .for_each(|j| {
                     self.board[i][j] += 1;
-                    if self.board[i][j] > 9 {
-                        triggers.push((i, j));
+                    if self.board[i][j] > self.rules.threshold {
+                        wave.push((i, j));
                         flashes.insert((i, j));
-                        self.board[i][j] = 0;
                     }
                 }));
-        while !triggers.is_empty() {
-            let (x, y) = triggers.pop().unwrap();
-            self.neighbours(x, y)
-                .iter()
-                .for_each(|(nx, ny)| {
-                    if !flashes.contains(&(*nx, *ny)) {
-                        triggers.push((*nx, *ny));
-                        flashes.insert((*nx, *ny));
-                        self.board[*nx][*ny] = 0;
+        let mut depth = 0;
+        while !wave.is_empty() {
+            depth += 1;
+            let mut next_wave = vec![];
+            for (x, y) in wave {
+                for (nx, ny) in self.neighbours(x, y) {
+                    self.board[nx][ny] += 1;
+                    if self.board[nx][ny] > self.rules.threshold && flashes.insert((nx, ny)) {
+                        next_wave.push((nx, ny));
                     }
-                });
+                }
+            }
+            wave = next_wave;
+        }
+        flashes.iter().for_each(|&(x, y)| self.board[x][y] = 0);
+        let largest_chain = self.largest_cluster(&flashes);
+        StepStats { flashes, depth, largest_chain }
+    }
+
+    fn largest_cluster(&self, cells: &HashSet<(usize, usize)>) -> usize {
+        let mut visited: HashSet<(usize, usize)> = HashSet::new();
+        let mut largest = 0;
+        for &cell in cells {
+            if !visited.insert(cell) {
+                continue;
+            }
+            let mut size = 0;
+            let mut to_check = vec![cell];
+            while let Some((x, y)) = to_check.pop() {
+                size += 1;
+                self.neighbours(x, y)
+                    .into_iter()
+                    .filter(|neighbour| cells.contains(neighbour))
+                    .filter(|&neighbour| visited.insert(neighbour))
+                    .for_each(|neighbour| to_check.push(neighbour));
+            }
+            largest = largest.max(size);
         }
-        println!("{}", flashes.len());
-        flashes
+        largest
+    }
+}
+
+struct StepStats {
+    flashes: HashSet<(usize, usize)>,
+    depth: usize,
+    largest_chain: usize,
+}
+
+struct Cycle {
+    start: usize,
+    length: usize,
+    flashes: Vec<usize>,
+}
+
+impl Cycle {
+    fn detect(board: &Board) -> Cycle {
+        let mut board = board.clone();
+        let mut seen: HashMap<Vec<Vec<i32>>, usize> = HashMap::new();
+        let mut flashes = vec![];
+        loop {
+            if let Some(&start) = seen.get(&board.board) {
+                return Cycle { start, length: flashes.len() - start, flashes };
+            }
+            seen.insert(board.board.clone(), flashes.len());
+            flashes.push(board.step().len());
+        }
+    }
+
+    fn total_flashes(&self, steps: u64) -> u128 {
+        let prefix = steps.min(self.start as u64);
+        let head: u128 = self.flashes[..prefix as usize].iter().map(|&count| count as u128).sum();
+        let remaining = steps - prefix;
+        let cycle_sum: u128 = self.flashes[self.start..].iter().map(|&count| count as u128).sum();
+        let partial: u128 = self.flashes[self.start..self.start + (remaining % self.length as u64) as usize]
+            .iter()
+            .map(|&count| count as u128)
+            .sum();
+        head + (remaining / self.length as u64) as u128 * cycle_sum + partial
+    }
+
+    fn first_synchronized(&self, size: usize) -> Option<u64> {
+        self.flashes.iter()
+            .position(|&count| count == size)
+            .map(|index| index as u64 + 1)
     }
 }
 
 fn part1(board: &Board) -> i32 {
     let mut board = board.clone();
     (0..100)
-        .map(|index| board.step().len() as i32)
+        .map(|_| board.step().len() as i32)
         .sum()
 }
 
-fn part2(board: &Board) -> i32 {
-    let mut board = board.clone();
-    let mut counter = 0;
-    loop {
-        counter += 1;
-        let res = board.step();
-        if res.len() == 100 {
-            return counter;
-        }
-    }
+fn part2(board: &Board) -> Option<u64> {
+    Cycle::detect(board).first_synchronized(board.size())
 }
 
 pub(crate) fn solve() {
@@ -85,8 +217,25 @@ pub(crate) fn solve() {
         board: read_to_string("11.txt").unwrap()
             .lines()
             .map(|line| line.chars().map(|x| x.to_string().parse().unwrap()).collect_vec())
-            .collect_vec()
+            .collect_vec(),
+        rules: Rules::from_args(),
     };
     println!("{}", part1(&board));
-    println!("{}", part2(&board));
+    if std::env::args().any(|arg| arg == "--verbose") {
+        let mut board = board.clone();
+        for step in 1..101 {
+            let stats = board.step_with_stats();
+            println!("step {}: {} flashes, cascade depth {}, largest chain {}",
+                     step, stats.flashes.len(), stats.depth, stats.largest_chain);
+        }
+    }
+    match part2(&board) {
+        Some(step) => println!("{}", step),
+        None => println!("never synchronized"),
+    }
+    if let Some(steps) = std::env::args().find_map(|arg| arg.strip_prefix("--steps=").map(|steps| steps.parse::<u64>().unwrap())) {
+        let cycle = Cycle::detect(&board);
+        println!("cycle starts at step {} with length {}", cycle.start, cycle.length);
+        println!("{}", cycle.total_flashes(steps));
+    }
 }
\ No newline at end of file
@@ -1,3 +1,4 @@
+use std::fmt;
 use std::fs;
 use std::ops::Not;
 use itertools::Itertools;
@@ -19,22 +20,6 @@ impl Numbers {
                 .collect_vec()
         }
     }
-    fn filter_by_bit_pos(&self, pos: usize, bit: bool) -> Numbers {
-        if self.is_singular() {
-            self.clone()
-        } else {
-            Numbers {
-                numbers: self.numbers
-                    .iter()
-                    .filter(|&number| number.has_bit_at_position(pos, bit))
-                    .map(BinNumber::clone)
-                    .collect_vec()
-            }
-        }
-    }
-    fn is_singular(&self) -> bool {
-        self.numbers.len() == 1
-    }
     fn get_one(&self) -> BinNumber {
         self.numbers[0].clone()
     }
@@ -57,10 +42,6 @@ impl BinNumber {
         self.value[pos]
     }
 
-    fn has_bit_at_position(&self, pos: usize, bit: bool) -> bool {
-        self.bit_at_position(pos) == bit
-    }
-
     fn bit_size(&self) -> usize {
         self.value.len()
     }
@@ -72,14 +53,6 @@ impl BinNumber {
             .count()
     }
 
-    fn most_common(&self) -> bool {
-        self.count_bits(true) >= self.count_bits(false)
-    }
-
-    fn least_common(&self) -> bool {
-        self.most_common().not()
-    }
-
     fn negate(&self) -> BinNumber {
         BinNumber {
             value: self.value.iter()
@@ -88,35 +61,191 @@ impl BinNumber {
         }
     }
 
-    fn to_int(&self) -> i32 {
-        let bin_string = self.value
+    fn to_bit_string(&self) -> String {
+        self.value
             .iter()
-            .fold("".to_string(), |acc, &x| acc + (x as i32).to_string().as_str());
-        i32::from_str_radix(bin_string.as_str(), 2).unwrap()
+            .map(|&x| if x { '1' } else { '0' })
+            .collect()
+    }
+
+    fn to_int(&self) -> i32 {
+        i32::from_str_radix(self.to_bit_string().as_str(), 2).unwrap()
+    }
+}
+
+struct ColumnStats {
+    ones: usize,
+    zeros: usize,
+}
+
+impl ColumnStats {
+    fn new(column: &BinNumber) -> ColumnStats {
+        ColumnStats {
+            ones: column.count_bits(true),
+            zeros: column.count_bits(false),
+        }
+    }
+
+    fn margin(&self) -> usize {
+        self.ones.abs_diff(self.zeros)
     }
+
+    fn is_tie(&self) -> bool {
+        self.ones == self.zeros
+    }
+}
+
+struct PowerReport {
+    columns: Vec<ColumnStats>,
+    gamma: BinNumber,
+    epsilon: BinNumber,
 }
 
-fn gases(numbers: &Numbers, common: fn(&BinNumber) -> bool) -> i32 {
-    (0..numbers.bit_size())
-        .fold(numbers.clone(), |acc, pos| acc.filter_by_bit_pos(pos, common(&acc.bits_at(pos))))
-        .get_one()
-        .to_int()
+impl PowerReport {
+    fn new(numbers: &Numbers) -> PowerReport {
+        let columns = (0..numbers.bit_size())
+            .map(|bit| numbers.bits_at(bit))
+            .map(|column| ColumnStats::new(&column))
+            .collect_vec();
+        let gamma = BinNumber {
+            value: columns.iter()
+                .map(|column| column.ones >= column.zeros)
+                .collect_vec()
+        };
+        let epsilon = gamma.negate();
+        PowerReport { columns, gamma, epsilon }
+    }
+
+    fn power_consumption(&self) -> i32 {
+        self.gamma.to_int() * self.epsilon.to_int()
+    }
+
+    fn ties(&self) -> Vec<usize> {
+        self.columns.iter()
+            .positions(|column| column.is_tie())
+            .collect_vec()
+    }
+}
+
+impl fmt::Display for PowerReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "pos  ones zeros margin tie")?;
+        for (pos, column) in self.columns.iter().enumerate() {
+            writeln!(f, "{:>3} {:>5} {:>5} {:>6} {}", pos, column.ones, column.zeros, column.margin(),
+                     if column.is_tie() { "yes" } else { "no" })?;
+        }
+        writeln!(f, "gamma   {} = {}", self.gamma.to_bit_string(), self.gamma.to_int())?;
+        write!(f, "epsilon {} = {}", self.epsilon.to_bit_string(), self.epsilon.to_int())
+    }
+}
+
+#[derive(Clone, Copy)]
+enum TieBreak {
+    One,
+    Zero,
+}
+
+impl TieBreak {
+    fn bit(&self) -> bool {
+        match self {
+            TieBreak::One => true,
+            TieBreak::Zero => false,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Rating {
+    MostCommon,
+    LeastCommon,
+}
+
+struct TrieNode {
+    children: [Option<usize>; 2],
+    count: usize,
+}
+
+struct Trie {
+    nodes: Vec<TrieNode>,
+    bit_size: usize,
+}
+
+impl Trie {
+    fn new(bit_size: usize) -> Trie {
+        Trie {
+            nodes: vec![TrieNode { children: [None, None], count: 0 }],
+            bit_size,
+        }
+    }
+
+    fn from_lines(contents: &str) -> Trie {
+        let mut lines = contents.lines().peekable();
+        let bit_size = lines.peek().map(|line| line.len()).unwrap_or(0);
+        let mut trie = Trie::new(bit_size);
+        lines.for_each(|line| trie.insert(line.bytes().map(|c| c == b'1')));
+        trie
+    }
+
+    fn insert(&mut self, bits: impl Iterator<Item=bool>) {
+        let mut node = 0;
+        self.nodes[node].count += 1;
+        for bit in bits {
+            node = match self.nodes[node].children[bit as usize] {
+                Some(child) => child,
+                None => {
+                    self.nodes.push(TrieNode { children: [None, None], count: 0 });
+                    let child = self.nodes.len() - 1;
+                    self.nodes[node].children[bit as usize] = Some(child);
+                    child
+                }
+            };
+            self.nodes[node].count += 1;
+        }
+    }
+
+    fn subtree_count(&self, node: usize, bit: bool) -> usize {
+        self.nodes[node].children[bit as usize]
+            .map(|child| self.nodes[child].count)
+            .unwrap_or(0)
+    }
+
+    fn choose_bit(&self, node: usize, rating: Rating, tie: TieBreak) -> bool {
+        let ones = self.subtree_count(node, true);
+        let zeros = self.subtree_count(node, false);
+        if ones == 0 || zeros == 0 {
+            return ones > 0;
+        }
+        match (rating, ones.cmp(&zeros)) {
+            (_, std::cmp::Ordering::Equal) => tie.bit(),
+            (Rating::MostCommon, order) => order.is_gt(),
+            (Rating::LeastCommon, order) => order.is_lt(),
+        }
+    }
+
+    fn rating(&self, rating: Rating, tie: TieBreak) -> Option<i64> {
+        let mut node = 0;
+        let mut value = 0;
+        for _ in 0..self.bit_size {
+            let bit = self.choose_bit(node, rating, tie);
+            node = self.nodes[node].children[bit as usize]?;
+            value = value * 2 + bit as i64;
+        }
+        Some(value)
+    }
+
+    fn life_support(&self, oxygen_tie: TieBreak, co2_tie: TieBreak) -> Option<i64> {
+        let oxygen = self.rating(Rating::MostCommon, oxygen_tie)?;
+        let co2 = self.rating(Rating::LeastCommon, co2_tie)?;
+        Some(oxygen * co2)
+    }
 }
 
 fn part1(numbers: &Numbers) -> i32 {
-    let gamma_bits = (0..numbers.bit_size())
-        .map(|bit| numbers.bits_at(bit))
-        .map(|num| num.most_common())
-        .collect_vec();
-    let gamma = BinNumber { value: gamma_bits };
-    let eps = gamma.negate();
-    gamma.to_int() * eps.to_int()
+    PowerReport::new(numbers).power_consumption()
 }
 
-fn part2(numbers: &Numbers) -> i32 {
-    let oxygen = gases(numbers, BinNumber::most_common);
-    let co2 = gases(numbers, BinNumber::least_common);
-    oxygen * co2
+fn part2(trie: &Trie) -> i64 {
+    trie.life_support(TieBreak::One, TieBreak::Zero).unwrap()
 }
 
 pub(crate) fn solve() {
@@ -126,6 +255,13 @@ pub(crate) fn solve() {
             .map(|line| BinNumber::new(line))
             .collect()
     };
+    if std::env::args().any(|arg| arg == "--verbose") {
+        let report = PowerReport::new(&numbers);
+        println!("{}", report);
+        if !report.ties().is_empty() {
+            println!("tied columns: {:?}", report.ties());
+        }
+    }
     println!("{}", part1(&numbers));
-    println!("{}", part2(&numbers));
+    println!("{}", part2(&Trie::from_lines(&contents)));
 }
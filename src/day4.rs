@@ -1,47 +1,101 @@
+use std::collections::HashMap;
+use std::fmt;
 use std::fs;
 use itertools::Itertools;
 
-#[derive(Clone)]
-struct Field {
-    value: i32,
-    marked: bool,
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum WinningLine {
+    Row(usize),
+    Column(usize),
+    Diagonal,
+    AntiDiagonal,
+    Blackout,
 }
 
-impl Field {
-    fn is_marked(&self) -> bool {
-        self.marked
+impl fmt::Display for WinningLine {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WinningLine::Row(idx) => write!(f, "row {}", idx),
+            WinningLine::Column(idx) => write!(f, "column {}", idx),
+            WinningLine::Diagonal => write!(f, "diagonal"),
+            WinningLine::AntiDiagonal => write!(f, "anti-diagonal"),
+            WinningLine::Blackout => write!(f, "blackout"),
+        }
     }
-    fn mark(&self, value: i32) -> Field {
-        Field { value: self.value, marked: self.marked | (self.value == value) }
+}
+
+#[derive(Clone, Copy)]
+struct Rules {
+    diagonals: bool,
+    blackout: bool,
+    lines_to_win: usize,
+}
+
+impl Rules {
+    fn standard() -> Rules {
+        Rules { diagonals: false, blackout: false, lines_to_win: 1 }
+    }
+
+    fn from_args() -> Result<Rules, String> {
+        let args = std::env::args().collect_vec();
+        let standard = Rules::standard();
+        Ok(Rules {
+            diagonals: standard.diagonals | args.iter().any(|arg| arg == "--diagonals"),
+            blackout: standard.blackout | args.iter().any(|arg| arg == "--blackout"),
+            lines_to_win: match args.iter().find_map(|arg| arg.strip_prefix("--lines=")) {
+                Some(n) => n.parse().map_err(|_| format!("invalid line count: {}", n))?,
+                None => standard.lines_to_win,
+            },
+        })
     }
 }
 
 #[derive(Clone)]
 struct Board {
-    numbers: Vec<Vec<Field>>,
+    numbers: Vec<Vec<i32>>,
+    marked: Vec<Vec<bool>>,
+    row_hits: Vec<usize>,
+    col_hits: Vec<usize>,
+    diagonal_hits: [usize; 2],
+    marked_count: usize,
+    completed_lines: usize,
+    rules: Rules,
+    winning_line: Option<WinningLine>,
 }
 
 impl Board {
-    fn new(block: &str) -> Board {
-        Board {
-            numbers: block.lines()
-                .map(|line| line.split_whitespace()
-                    .map(|element| Field { value: element.parse().unwrap(), marked: false })
-                    .collect_vec())
-                .collect_vec()
+    fn new(block: &str, rules: Rules) -> Result<Board, String> {
+        let numbers = block.lines()
+            .map(|line| line.split_whitespace()
+                .map(|element| element.parse().map_err(|_| format!("invalid number: {}", element)))
+                .collect::<Result<Vec<i32>, String>>())
+            .collect::<Result<Vec<_>, String>>()?;
+        let rows = numbers.len();
+        let cols = numbers.first().map_or(0, |row| row.len());
+        if rows == 0 || cols == 0 {
+            return Err("empty board".to_string());
         }
-    }
-    fn mark(&self, value: i32) -> Board {
-        Board {
-            numbers: self.numbers.iter()
-                .map(|row| row.iter()
-                    .map(|field| field.mark(value))
-                    .collect_vec())
-                .collect_vec()
+        if numbers.iter().any(|row| row.len() != cols) {
+            return Err(format!("board rows must all have {} numbers", cols));
         }
-    }
-    fn is_winning(&self) -> bool {
-        self.is_row_marked() | self.is_column_marked()
+        if rules.diagonals && rows != cols {
+            return Err(format!("diagonals require a square board, got {}x{}", rows, cols));
+        }
+        let max_lines = rows + cols + if rules.diagonals { 2 } else { 0 };
+        if !rules.blackout && (rules.lines_to_win == 0 || rules.lines_to_win > max_lines) {
+            return Err(format!("cannot complete {} lines on a {}x{} board", rules.lines_to_win, rows, cols));
+        }
+        Ok(Board {
+            numbers,
+            marked: vec![vec![false; cols]; rows],
+            row_hits: vec![0; rows],
+            col_hits: vec![0; cols],
+            diagonal_hits: [0, 0],
+            marked_count: 0,
+            completed_lines: 0,
+            rules,
+            winning_line: None,
+        })
     }
     fn cols(&self) -> usize {
         self.numbers[0].len()
@@ -49,60 +103,127 @@ impl Board {
     fn rows(&self) -> usize {
         self.numbers.len()
     }
-    fn is_row_marked(&self) -> bool {
-        (0..self.rows())
-            .any(|row_idx| (0..self.cols())
-                .all(|col_idx| self.numbers[row_idx][col_idx].is_marked()))
-    }
-    fn is_column_marked(&self) -> bool {
-        (0..self.cols())
-            .any(|col_idx| (0..self.rows())
-                .all(|row_idx| self.numbers[row_idx][col_idx].is_marked()))
+    fn mark(&mut self, row: usize, col: usize) -> Option<WinningLine> {
+        if self.marked[row][col] {
+            return None;
+        }
+        self.marked[row][col] = true;
+        self.marked_count += 1;
+        self.row_hits[row] += 1;
+        self.col_hits[col] += 1;
+        let mut completed = vec![];
+        if self.row_hits[row] == self.cols() {
+            completed.push(WinningLine::Row(row));
+        }
+        if self.col_hits[col] == self.rows() {
+            completed.push(WinningLine::Column(col));
+        }
+        if self.rules.diagonals && row == col {
+            self.diagonal_hits[0] += 1;
+            if self.diagonal_hits[0] == self.rows() {
+                completed.push(WinningLine::Diagonal);
+            }
+        }
+        if self.rules.diagonals && row + col + 1 == self.cols() {
+            self.diagonal_hits[1] += 1;
+            if self.diagonal_hits[1] == self.rows() {
+                completed.push(WinningLine::AntiDiagonal);
+            }
+        }
+        self.completed_lines += completed.len();
+        if self.winning_line.is_some() {
+            return None;
+        }
+        self.winning_line = if self.rules.blackout {
+            Some(WinningLine::Blackout).filter(|_| self.marked_count == self.rows() * self.cols())
+        } else if self.completed_lines >= self.rules.lines_to_win {
+            completed.first().copied()
+        } else {
+            None
+        };
+        self.winning_line
     }
     fn get_unmarked(&self) -> Vec<i32> {
         self.numbers.iter()
-            .flat_map(|row| row.iter()
-                .filter(|element| element.is_marked() == false)
-                .map(|x| x.value))
+            .zip(self.marked.iter())
+            .flat_map(|(row, marks)| row.iter()
+                .zip(marks.iter())
+                .filter(|(_, &marked)| !marked)
+                .map(|(&value, _)| value))
             .collect_vec()
     }
+    fn get_score(&self) -> i32 {
+        self.get_unmarked().iter().sum()
+    }
 }
 
+#[derive(Clone)]
 struct Boards {
     boards: Vec<Board>,
+    index: HashMap<i32, Vec<(usize, usize, usize)>>,
+    won_count: usize,
 }
 
 impl Boards {
-    fn mark(&self, value: i32) -> Boards {
-        Boards {
-            boards: self.boards.iter()
-                .map(|board| board.mark(value))
-                .collect_vec()
+    fn new(boards: Vec<Board>) -> Boards {
+        let mut index: HashMap<i32, Vec<(usize, usize, usize)>> = HashMap::new();
+        for (board_idx, board) in boards.iter().enumerate() {
+            for (row_idx, row) in board.numbers.iter().enumerate() {
+                for (col_idx, &value) in row.iter().enumerate() {
+                    index.entry(value).or_default().push((board_idx, row_idx, col_idx));
+                }
+            }
         }
+        Boards { boards, index, won_count: 0 }
     }
-    fn is_any_winning(&self) -> bool {
-        self.boards
-            .iter()
-            .any(|board| board.is_winning())
+    fn mark(&mut self, value: i32) -> Vec<(usize, WinningLine)> {
+        let mut winners = vec![];
+        for &(board_idx, row, col) in self.index.get(&value).into_iter().flatten() {
+            if let Some(line) = self.boards[board_idx].mark(row, col) {
+                winners.push((board_idx, line));
+            }
+        }
+        self.won_count += winners.len();
+        winners.into_iter().sorted_by_key(|&(board_idx, _)| board_idx).collect_vec()
     }
     fn is_all_winning(&self) -> bool {
-        self.boards
-            .iter()
-            .all(|board| board.is_winning())
+        self.won_count == self.boards.len()
     }
-    fn get_winning_score(&self) -> i32 {
-        self.boards.iter()
-            .find_or_first(|&board| board.is_winning())
-            .map(|board| board.get_unmarked().iter().sum())
-            .unwrap()
+    fn get_score(&self, board_idx: usize) -> i32 {
+        self.boards[board_idx].get_score()
     }
-    fn filter_out_winning(&self) -> Boards {
-        Boards {
-            boards: self.boards.iter()
-                .filter(|board| board.is_winning() == false)
-                .map(|board| board.clone())
-                .collect_vec()
+}
+
+struct BoardResult {
+    board: usize,
+    draw_index: usize,
+    number: i32,
+    line: WinningLine,
+    unmarked_sum: i32,
+    score: i32,
+}
+
+struct GameResult {
+    draws: Vec<i32>,
+    winners: Vec<BoardResult>,
+    never_won: Vec<usize>,
+}
+
+impl GameResult {
+    fn replay(&self) -> String {
+        let mut winners = self.winners.iter().peekable();
+        let mut lines = vec![];
+        for (draw_index, number) in self.draws.iter().enumerate() {
+            lines.push(format!("draw {}: {}", draw_index + 1, number));
+            while let Some(result) = winners.next_if(|result| result.draw_index == draw_index) {
+                lines.push(format!("  board {} wins on {} with {} (unmarked {}, score {})",
+                                   result.board, result.line, result.number, result.unmarked_sum, result.score));
+            }
+        }
+        if !self.never_won.is_empty() {
+            lines.push(format!("never won: {}", self.never_won.iter().join(", ")));
         }
+        lines.join("\n")
     }
 }
 
@@ -112,30 +233,52 @@ struct InputData {
 }
 
 fn part1(data: &InputData) -> Option<i32> {
-    let mut boards = data.boards.filter_out_winning();
+    let mut boards = data.boards.clone();
     for number in data.numbers.iter() {
-        boards = boards.mark(*number);
-        if boards.is_any_winning() {
-            return Some(boards.get_winning_score() * number);
+        let winners = boards.mark(*number);
+        if let Some(&(first, _)) = winners.first() {
+            return Some(boards.get_score(first) * number);
         }
     }
     return None;
 }
 
 fn part2(data: &InputData) -> Option<i32> {
-    let mut boards = data.boards.filter_out_winning();
+    let mut boards = data.boards.clone();
     for number in data.numbers.iter() {
-        boards = boards.mark(*number);
+        let winners = boards.mark(*number);
         if boards.is_all_winning() {
-            return Some(boards.get_winning_score() * number);
-        } else {
-            boards = boards.filter_out_winning();
+            return winners.first().map(|&(last, _)| boards.get_score(last) * number);
         }
     }
     None
 }
 
-fn load_data(name: &str) -> InputData {
+fn play(data: &InputData) -> GameResult {
+    let mut boards = data.boards.clone();
+    let mut winners = vec![];
+    let mut played = 0;
+    for (draw_index, &number) in data.numbers.iter().enumerate() {
+        played += 1;
+        for (board, line) in boards.mark(number) {
+            let unmarked_sum = boards.get_score(board);
+            winners.push(BoardResult { board, draw_index, number, line, unmarked_sum, score: unmarked_sum * number });
+        }
+        if boards.is_all_winning() {
+            break;
+        }
+    }
+    let never_won = (0..boards.boards.len())
+        .filter(|&board| !winners.iter().any(|result| result.board == board))
+        .collect_vec();
+    GameResult {
+        draws: data.numbers[..played].to_vec(),
+        winners,
+        never_won,
+    }
+}
+
+fn load_data(name: &str, rules: Rules) -> InputData {
     let contents = fs::read_to_string(name).unwrap();
     let blocks = contents.split("\r\n\r\n").collect_vec();
     let numbers: Vec<i32> = blocks[0].split(",")
@@ -143,13 +286,24 @@ fn load_data(name: &str) -> InputData {
         .collect_vec();
     let boards = blocks[1..]
         .iter()
-        .map(|&block| Board::new(block))
-        .collect_vec();
-    InputData { numbers, boards: Boards { boards } }
+        .map(|&block| Board::new(block, rules))
+        .collect::<Result<Vec<_>, String>>()
+        .unwrap();
+    InputData { numbers, boards: Boards::new(boards) }
 }
 
 pub(crate) fn solve() {
-    let data = load_data("4.txt");
+    let rules = match Rules::from_args() {
+        Ok(rules) => rules,
+        Err(error) => {
+            eprintln!("error: {}", error);
+            return;
+        }
+    };
+    let data = load_data("4.txt", rules);
+    if std::env::args().any(|arg| arg == "--verbose") {
+        println!("{}", play(&data).replay());
+    }
     println!("{}", part1(&data).unwrap());
     println!("{}", part2(&data).unwrap());
 }
@@ -1,11 +1,60 @@
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use itertools::Itertools;
 
+const MAX_HEATMAP_PIXELS: i64 = 25_000_000;
+
 struct Point {
     x: i32,
     y: i32,
 }
 
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum Direction {
+    Horizontal,
+    Vertical,
+    Diagonal,
+    AntiDiagonal,
+}
+
+impl Direction {
+    fn coefficients(&self) -> (i64, i64) {
+        match self {
+            Direction::Horizontal => (0, 1),
+            Direction::Vertical => (1, 0),
+            Direction::Diagonal => (1, -1),
+            Direction::AntiDiagonal => (1, 1),
+        }
+    }
+    fn carrier_key(&self, x: i64, y: i64) -> i64 {
+        let (a, b) = self.coefficients();
+        a * x + b * y
+    }
+    fn position(&self, x: i64, y: i64) -> i64 {
+        match self {
+            Direction::Vertical => y,
+            _ => x,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum LineClass {
+    Straight,
+    Diagonal,
+    Other,
+}
+
+#[derive(Clone, Copy)]
+enum Raster {
+    Bresenham,
+    Lattice,
+}
+
+fn gcd(a: i32, b: i32) -> i32 {
+    if b == 0 { a.abs() } else { gcd(b, a % b) }
+}
+
 struct Line {
     p1: Point,
     p2: Point,
@@ -22,67 +71,254 @@ impl Line {
             p2: Point { x: numbers[2], y: numbers[3] },
         }
     }
+    fn class(&self) -> LineClass {
+        if (self.p1.x == self.p2.x) | (self.p1.y == self.p2.y) {
+            LineClass::Straight
+        } else if (self.p2.x - self.p1.x).abs() == (self.p2.y - self.p1.y).abs() {
+            LineClass::Diagonal
+        } else {
+            LineClass::Other
+        }
+    }
     fn is_straight(&self) -> bool {
-        return (self.p1.x == self.p2.x) | (self.p1.y == self.p2.y);
-    }
-    fn delta(&self, start: i32, end: i32) -> (i32, i32) {
-        let length = (end - start).abs();
-        let delta = if length > 0 { (end - start) / length } else { 0 };
-        return (length, delta);
-    }
-    fn covered_fields(&self) -> Vec<(usize, usize)> {
-        let (length_x, delta_x) = self.delta(self.p1.x, self.p2.x);
-        let (length_y, delta_y) = self.delta(self.p1.y, self.p2.y);
-        return (0..(length_x).max(length_y) + 1)
-            .map(|i| ((self.p1.x + delta_x * i) as usize, (self.p1.y + delta_y * i) as usize))
+        self.class() == LineClass::Straight
+    }
+    fn is_diagonal(&self) -> bool {
+        self.class() == LineClass::Diagonal
+    }
+    fn direction(&self) -> Option<Direction> {
+        let dx = self.p2.x - self.p1.x;
+        let dy = self.p2.y - self.p1.y;
+        if dy == 0 {
+            Some(Direction::Horizontal)
+        } else if dx == 0 {
+            Some(Direction::Vertical)
+        } else if dx == dy {
+            Some(Direction::Diagonal)
+        } else if dx == -dy {
+            Some(Direction::AntiDiagonal)
+        } else {
+            None
+        }
+    }
+    fn carrier(&self) -> Option<((Direction, i64), (i64, i64))> {
+        let direction = self.direction()?;
+        let (x1, y1) = (self.p1.x as i64, self.p1.y as i64);
+        let (x2, y2) = (self.p2.x as i64, self.p2.y as i64);
+        let start = direction.position(x1, y1);
+        let end = direction.position(x2, y2);
+        Some(((direction, direction.carrier_key(x1, y1)), (start.min(end), start.max(end))))
+    }
+    fn lattice_points(&self) -> Vec<(i32, i32)> {
+        let dx = self.p2.x - self.p1.x;
+        let dy = self.p2.y - self.p1.y;
+        let steps = gcd(dx, dy);
+        if steps == 0 {
+            return vec![(self.p1.x, self.p1.y)];
+        }
+        return (0..steps + 1)
+            .map(|i| (self.p1.x + dx / steps * i, self.p1.y + dy / steps * i))
             .collect_vec();
     }
+    fn bresenham_points(&self) -> Vec<(i32, i32)> {
+        let dx = (self.p2.x - self.p1.x).abs();
+        let dy = -(self.p2.y - self.p1.y).abs();
+        let step_x = (self.p2.x - self.p1.x).signum();
+        let step_y = (self.p2.y - self.p1.y).signum();
+        let (mut x, mut y) = (self.p1.x, self.p1.y);
+        let mut error = dx + dy;
+        let mut points = vec![(x, y)];
+        while (x, y) != (self.p2.x, self.p2.y) {
+            let doubled = 2 * error;
+            if doubled >= dy {
+                error += dy;
+                x += step_x;
+            }
+            if doubled <= dx {
+                error += dx;
+                y += step_y;
+            }
+            points.push((x, y));
+        }
+        points
+    }
+    fn covered_fields(&self, raster: Raster) -> Vec<(i32, i32)> {
+        match raster {
+            Raster::Bresenham => self.bresenham_points(),
+            Raster::Lattice => self.lattice_points(),
+        }
+    }
 }
 
 struct Board {
-    board: Vec<Vec<i32>>,
+    board: HashMap<(i32, i32), i32>,
 }
 
 impl Board {
-    fn new(x: i32, y: i32) -> Board {
+    fn new() -> Board {
         return Board {
-            board: (0..x)
-                .map(|_| (0..y)
-                    .map(|_| 0)
-                    .collect_vec())
-                .collect_vec()
+            board: HashMap::new()
         };
     }
     fn count_above_threshold(&self, bound: i32) -> i32 {
-        return self.board.iter()
-            .flat_map(|row| row.iter())
+        return self.board.values()
             .filter(|&&field| field >= bound)
             .count() as i32;
     }
 
-    fn mark_field(mut self, x: &usize, y: &usize) -> Board {
-        self.board[*x][*y] += 1;
+    fn hotspots(&self, bound: i32) -> Vec<((i32, i32), i32)> {
+        self.board.iter()
+            .filter(|(_, &field)| field >= bound)
+            .map(|(&point, &field)| (point, field))
+            .sorted_by_key(|&((x, y), field)| (-field, y, x))
+            .collect_vec()
+    }
+
+    fn histogram(&self) -> Vec<(i32, usize)> {
+        self.board.values()
+            .copied()
+            .counts()
+            .into_iter()
+            .sorted()
+            .collect_vec()
+    }
+
+    fn bounds(&self) -> Option<((i32, i32), (i32, i32))> {
+        let (min_x, max_x) = self.board.keys().map(|&(x, _)| x).minmax().into_option()?;
+        let (min_y, max_y) = self.board.keys().map(|&(_, y)| y).minmax().into_option()?;
+        Some(((min_x, min_y), (max_x, max_y)))
+    }
+
+    fn to_pgm(&self) -> Result<String, String> {
+        let ((min_x, min_y), (max_x, max_y)) = self.bounds().unwrap_or(((0, 0), (0, 0)));
+        let (width, height) = (max_x as i64 - min_x as i64 + 1, max_y as i64 - min_y as i64 + 1);
+        if width * height > MAX_HEATMAP_PIXELS {
+            return Err(format!("heatmap of {}x{} exceeds {} pixels", width, height, MAX_HEATMAP_PIXELS));
+        }
+        let max_value = self.board.values().copied().max().unwrap_or(0).max(1);
+        let rows = (min_y..max_y + 1)
+            .map(|y| (min_x..max_x + 1)
+                .map(|x| self.board.get(&(x, y)).unwrap_or(&0).to_string())
+                .join(" "))
+            .join("\n");
+        Ok(format!("P2\n{} {}\n{}\n{}\n", width, height, max_value, rows))
+    }
+
+    fn mark_field(mut self, x: &i32, y: &i32) -> Board {
+        *self.board.entry((*x, *y)).or_insert(0) += 1;
         return self;
     }
 
-    fn mark_line(self, line: &Line) -> Board {
-        line.covered_fields()
+    fn mark_line(self, line: &Line, raster: Raster) -> Board {
+        line.covered_fields(raster)
             .iter()
             .fold(self, |board, (x, y)| board.mark_field(x, y))
     }
 }
 
-fn part1(lines: &Vec<Line>) -> i32 {
-    lines.iter()
-        .filter(|line| line.is_straight())
-        .fold(Board::new(1000, 1000), |board, line| board.mark_line(line))
-        .count_above_threshold(2)
+struct Carrier {
+    intervals: Vec<(i64, i64)>,
+}
+
+impl Carrier {
+    fn coverage(&self, position: i64) -> usize {
+        self.intervals.iter()
+            .filter(|&&(start, end)| (start <= position) & (position <= end))
+            .count()
+    }
+
+    fn overlap_size(&self) -> i64 {
+        let events = self.intervals.iter()
+            .flat_map(|&(start, end)| [(start, 1), (end + 1, -1)])
+            .sorted()
+            .collect_vec();
+        let mut depth = 0;
+        let mut size = 0;
+        for ((position, change), (next, _)) in events.iter().tuple_windows() {
+            depth += change;
+            if depth >= 2 {
+                size += next - position;
+            }
+        }
+        size
+    }
+}
+
+fn crossing(first: &(Direction, i64), second: &(Direction, i64)) -> Option<(i64, i64)> {
+    let ((a1, b1), k1) = (first.0.coefficients(), first.1);
+    let ((a2, b2), k2) = (second.0.coefficients(), second.1);
+    let det = a1 * b2 - a2 * b1;
+    if det == 0 {
+        return None;
+    }
+    let x = k1 * b2 - k2 * b1;
+    let y = a1 * k2 - a2 * k1;
+    if (x % det != 0) | (y % det != 0) {
+        return None;
+    }
+    Some((x / det, y / det))
+}
+
+fn count_overlaps_analytic<'a>(lines: impl Iterator<Item=&'a Line>) -> Option<i64> {
+    let mut carriers: HashMap<(Direction, i64), Carrier> = HashMap::new();
+    for line in lines {
+        let (key, interval) = line.carrier()?;
+        carriers.entry(key).or_insert(Carrier { intervals: vec![] }).intervals.push(interval);
+    }
+    let covers = |key: &(Direction, i64), (x, y): (i64, i64)| carriers.get(key)
+        .map_or(0, |carrier| carrier.coverage(key.0.position(x, y)));
+    let crossings: HashSet<(i64, i64)> = carriers.keys()
+        .tuple_combinations()
+        .filter_map(|(first, second)| crossing(first, second))
+        .collect();
+    let collinear: i64 = carriers.values()
+        .map(|carrier| carrier.overlap_size())
+        .sum();
+    let corrections: i64 = crossings.iter()
+        .map(|&(x, y)| {
+            let coverages = [Direction::Horizontal, Direction::Vertical, Direction::Diagonal, Direction::AntiDiagonal]
+                .iter()
+                .map(|&direction| covers(&(direction, direction.carrier_key(x, y)), (x, y)))
+                .collect_vec();
+            let covering_lines = coverages.iter().filter(|&&c| c >= 1).count();
+            let counted = coverages.iter().filter(|&&c| c >= 2).count() as i64;
+            if covering_lines >= 2 { 1 - counted } else { 0 }
+        })
+        .sum();
+    Some(collinear + corrections)
 }
 
-fn part2(lines: &Vec<Line>) -> i32 {
+fn build_board(lines: &Vec<Line>, classes: &Vec<LineClass>, raster: Raster) -> Board {
     lines.iter()
-        .fold(Board::new(1000, 1000), |board, line| board.mark_line(line))
-        .count_above_threshold(2)
+        .filter(|line| classes.contains(&line.class()))
+        .fold(Board::new(), |board, line| board.mark_line(line, raster))
+}
+
+fn part1(lines: &Vec<Line>) -> i64 {
+    count_overlaps_analytic(lines.iter().filter(|line| line.is_straight())).unwrap()
+}
+
+fn part2(lines: &Vec<Line>) -> i64 {
+    count_overlaps_analytic(lines.iter().filter(|line| line.is_straight() | line.is_diagonal())).unwrap()
+}
+
+fn parse_classes(arg: &str) -> Vec<LineClass> {
+    arg.split(",")
+        .map(|class| match class {
+            "straight" => LineClass::Straight,
+            "diagonal" => LineClass::Diagonal,
+            "other" => LineClass::Other,
+            _ => panic!("unknown line class: {}", class)
+        })
+        .collect_vec()
+}
+
+fn parse_raster(arg: &str) -> Raster {
+    match arg {
+        "bresenham" => Raster::Bresenham,
+        "lattice" => Raster::Lattice,
+        _ => panic!("unknown raster mode: {}", arg)
+    }
 }
 
 fn load_data(name: &str) -> Vec<Line> {
@@ -97,4 +333,79 @@ pub(crate) fn solve() {
     let lines = load_data("5.txt");
     println!("{}", part1(&lines));
     println!("{}", part2(&lines));
+    let args = std::env::args().collect_vec();
+    let classes = args.iter().find_map(|arg| arg.strip_prefix("--classes=")).map(parse_classes);
+    let raster = args.iter().find_map(|arg| arg.strip_prefix("--raster=")).map(parse_raster);
+    let verbose = args.iter().any(|arg| arg == "--verbose");
+    let hotspots = args.iter()
+        .find_map(|arg| arg.strip_prefix("--hotspots="))
+        .map(|bound| bound.parse::<i32>().unwrap());
+    let heatmap = args.iter().find_map(|arg| arg.strip_prefix("--heatmap="));
+    let custom = classes.is_some() | raster.is_some();
+    if !custom & !verbose & hotspots.is_none() & heatmap.is_none() {
+        return;
+    }
+    let classes = classes.unwrap_or(vec![LineClass::Straight, LineClass::Diagonal]);
+    let board = build_board(&lines, &classes, raster.unwrap_or(Raster::Lattice));
+    if custom {
+        println!("{}", board.count_above_threshold(2));
+    }
+    if verbose {
+        for (overlaps, count) in board.histogram() {
+            println!("{} overlaps: {} points", overlaps, count);
+        }
+    }
+    if let Some(bound) = hotspots {
+        for ((x, y), overlaps) in board.hotspots(bound) {
+            println!("hotspot {},{}: {}", x, y, overlaps);
+        }
+    }
+    if let Some(path) = heatmap {
+        match board.to_pgm() {
+            Ok(pgm) => fs::write(path, pgm).unwrap(),
+            Err(error) => eprintln!("error: {}", error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn count_overlaps(lines: &Vec<Line>, classes: &Vec<LineClass>) -> i64 {
+        build_board(lines, classes, Raster::Lattice).count_above_threshold(2) as i64
+    }
+
+    #[test]
+    fn analytic_matches_raster_on_example() {
+        let lines = ["0,9 -> 5,9", "8,0 -> 0,8", "9,4 -> 3,4", "2,2 -> 2,1", "7,0 -> 7,4",
+            "6,4 -> 2,0", "0,9 -> 2,9", "3,4 -> 1,4", "0,0 -> 8,8", "5,5 -> 8,2"]
+            .iter()
+            .map(|line| Line::new(line))
+            .collect_vec();
+        assert_eq!(part1(&lines), 5);
+        assert_eq!(part2(&lines), 12);
+        assert_eq!(part1(&lines), count_overlaps(&lines, &vec![LineClass::Straight]));
+        assert_eq!(part2(&lines), count_overlaps(&lines, &vec![LineClass::Straight, LineClass::Diagonal]));
+    }
+
+    #[test]
+    fn analytic_matches_raster_on_random_lines() {
+        let mut seed: u64 = 2021;
+        let mut next = |modulo: u64| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            ((seed >> 33) % modulo) as i32
+        };
+        for _ in 0..500 {
+            let lines = (0..1 + next(12))
+                .map(|_| {
+                    let (x, y, length) = (next(20) - 10, next(20) - 10, next(8));
+                    let (dx, dy) = [(1, 0), (0, 1), (1, 1), (1, -1)][next(4) as usize];
+                    Line::new(&format!("{},{} -> {},{}", x, y, x + dx * length, y + dy * length))
+                })
+                .collect_vec();
+            assert_eq!(part1(&lines), count_overlaps(&lines, &vec![LineClass::Straight]));
+            assert_eq!(part2(&lines), count_overlaps(&lines, &vec![LineClass::Straight, LineClass::Diagonal]));
+        }
+    }
 }
@@ -1,33 +1,288 @@
 use std::fs;
 use itertools::Itertools;
 use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+use std::ops::{Add, Mul};
 
-fn add(mut fish: HashMap<i32, i64>, key: i32, val: i64) -> HashMap<i32, i64> {
-    fish.insert(key, fish.get(&key).unwrap_or(&0) + val);
-    return fish;
+const MAX_CSV_DAYS: u64 = 1_000_000;
+
+fn add<K: Hash + Eq>(mut fish: HashMap<K, i64>, key: K, val: i64) -> Option<HashMap<K, i64>> {
+    let count = fish.entry(key).or_insert(0);
+    *count = count.checked_add(val)?;
+    return Some(fish);
 }
 
-fn part1(numbers: &Vec<i32>, rounds: i32) -> i64 {
-    let mut fish: HashMap<i32, i64> = HashMap::new();
-    for &number in numbers {
-        fish = add(fish, number, 1);
-    }
-    for _ in 0..rounds {
-        let mut new_fish: HashMap<i32, i64> = HashMap::new();
-        for (days, count) in fish {
-            match days {
-                0 => {
-                    new_fish = add(new_fish, 6, count);
-                    new_fish = add(new_fish, 8, count);
-                }
-                _ => {
-                    new_fish = add(new_fish, days - 1, count);
+fn total(fish: &HashMap<(i32, i32), i64>) -> Option<i64> {
+    fish.values().try_fold(0i64, |acc, &count| acc.checked_add(count))
+}
+
+struct Species {
+    name: String,
+    reset: i32,
+    newborn: i32,
+    death: Option<i32>,
+    timers: Vec<i32>,
+}
+
+impl Species {
+    fn lanternfish(timers: &Vec<i32>) -> Species {
+        Species { name: "lanternfish".to_string(), reset: 6, newborn: 8, death: None, timers: timers.clone() }
+    }
+
+    fn new(line: &str) -> Species {
+        let parts = line.split_whitespace().collect_vec();
+        Species {
+            name: parts[0].to_string(),
+            reset: parts[1].parse().unwrap(),
+            newborn: parts[2].parse().unwrap(),
+            death: match parts[3] {
+                "-" => None,
+                age => Some(age.parse().unwrap())
+            },
+            timers: parts[4].split(",").map(|x| x.parse().unwrap()).collect_vec(),
+        }
+    }
+
+    fn series(&self, rounds: i32) -> Option<Vec<i64>> {
+        let mut fish: HashMap<(i32, i32), i64> = HashMap::new();
+        for &number in self.timers.iter() {
+            fish = add(fish, (number, 0), 1)?;
+        }
+        let mut series = vec![total(&fish)?];
+        for _ in 0..rounds {
+            let mut new_fish: HashMap<(i32, i32), i64> = HashMap::new();
+            for ((days, age), count) in fish {
+                let age = if self.death.is_some() { age + 1 } else { age };
+                match days {
+                    0 => {
+                        new_fish = add(new_fish, (self.reset, age), count)?;
+                        new_fish = add(new_fish, (self.newborn, 0), count)?;
+                    }
+                    _ => {
+                        new_fish = add(new_fish, (days - 1, age), count)?;
+                    }
                 }
             }
+            fish = new_fish.into_iter()
+                .filter(|((_, age), _)| self.death.map_or(true, |death| *age < death))
+                .collect();
+            series.push(total(&fish)?);
+        }
+        Some(series)
+    }
+}
+
+struct Model {
+    species: Vec<Species>,
+}
+
+impl Model {
+    fn new(config: &str) -> Model {
+        Model {
+            species: config.lines()
+                .filter(|line| !line.trim().is_empty() && !line.starts_with("#"))
+                .map(|line| Species::new(line))
+                .collect_vec()
+        }
+    }
+
+    fn to_csv(&self, days: u64) -> Result<String, String> {
+        if days > MAX_CSV_DAYS {
+            return Err(format!("CSV export supports at most {} days, got {}", MAX_CSV_DAYS, days));
+        }
+        let rounds = days as i32;
+        let series = self.species.iter()
+            .map(|species| species.series(rounds)
+                .ok_or(format!("population of {} overflows i64 within {} days", species.name, days)))
+            .collect::<Result<Vec<_>, String>>()?;
+        let header = format!("day,{},total", self.species.iter().map(|species| &species.name).join(","));
+        let rows = (0..rounds as usize + 1)
+            .map(|day| format!("{},{},{}", day,
+                               series.iter().map(|counts| counts[day]).join(","),
+                               series.iter().map(|counts| counts[day] as i128).sum::<i128>()));
+        Ok(std::iter::once(header).chain(rows).join("\n") + "\n")
+    }
+}
+
+fn part1(numbers: &Vec<i32>, rounds: i32) -> i64 {
+    *Species::lanternfish(numbers).series(rounds).unwrap().last().unwrap()
+}
+
+#[derive(Clone)]
+struct BigUint {
+    limbs: Vec<u32>,
+}
+
+impl BigUint {
+    fn new(value: u64) -> BigUint {
+        BigUint { limbs: vec![value as u32, (value >> 32) as u32] }.normalized()
+    }
+
+    fn normalized(mut self) -> BigUint {
+        while self.limbs.last() == Some(&0) {
+            self.limbs.pop();
         }
-        fish = new_fish
+        self
+    }
+
+    fn div_small(&self, divisor: u32) -> (BigUint, u32) {
+        let mut remainder: u64 = 0;
+        let mut limbs = vec![0; self.limbs.len()];
+        for (i, &limb) in self.limbs.iter().enumerate().rev() {
+            let current = (remainder << 32) | limb as u64;
+            limbs[i] = (current / divisor as u64) as u32;
+            remainder = current % divisor as u64;
+        }
+        (BigUint { limbs }.normalized(), remainder as u32)
+    }
+}
+
+impl Add for BigUint {
+    type Output = BigUint;
+
+    fn add(self, other: BigUint) -> BigUint {
+        let mut limbs = vec![];
+        let mut carry: u64 = 0;
+        for i in 0..self.limbs.len().max(other.limbs.len()) {
+            let sum = *self.limbs.get(i).unwrap_or(&0) as u64 + *other.limbs.get(i).unwrap_or(&0) as u64 + carry;
+            limbs.push(sum as u32);
+            carry = sum >> 32;
+        }
+        limbs.push(carry as u32);
+        BigUint { limbs }.normalized()
+    }
+}
+
+impl Mul for BigUint {
+    type Output = BigUint;
+
+    fn mul(self, other: BigUint) -> BigUint {
+        let mut limbs = vec![0u32; self.limbs.len() + other.limbs.len() + 1];
+        for (i, &a) in self.limbs.iter().enumerate() {
+            let mut carry: u64 = 0;
+            for (j, &b) in other.limbs.iter().enumerate() {
+                let current = limbs[i + j] as u64 + a as u64 * b as u64 + carry;
+                limbs[i + j] = current as u32;
+                carry = current >> 32;
+            }
+            let mut k = i + other.limbs.len();
+            while carry > 0 {
+                let current = limbs[k] as u64 + carry;
+                limbs[k] = current as u32;
+                carry = current >> 32;
+                k += 1;
+            }
+        }
+        BigUint { limbs }.normalized()
+    }
+}
+
+impl fmt::Display for BigUint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut chunks = vec![];
+        let mut rest = self.clone();
+        while !rest.limbs.is_empty() {
+            let (quotient, remainder) = rest.div_small(1_000_000_000);
+            chunks.push(remainder);
+            rest = quotient;
+        }
+        match chunks.split_last() {
+            None => write!(f, "0"),
+            Some((first, others)) => {
+                write!(f, "{}", first)?;
+                others.iter().rev().try_for_each(|chunk| write!(f, "{:09}", chunk))
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Modular {
+    value: u64,
+    modulus: u64,
+}
+
+impl Add for Modular {
+    type Output = Modular;
+
+    fn add(self, other: Modular) -> Modular {
+        Modular { value: ((self.value as u128 + other.value as u128) % self.modulus as u128) as u64, modulus: self.modulus }
+    }
+}
+
+impl Mul for Modular {
+    type Output = Modular;
+
+    fn mul(self, other: Modular) -> Modular {
+        Modular { value: ((self.value as u128 * other.value as u128) % self.modulus as u128) as u64, modulus: self.modulus }
+    }
+}
+
+type Matrix<T> = Vec<Vec<T>>;
+
+fn matrix_mul<T: Clone + Add<Output=T> + Mul<Output=T>>(a: &Matrix<T>, b: &Matrix<T>) -> Matrix<T> {
+    (0..a.len())
+        .map(|i| (0..b[0].len())
+            .map(|j| (1..b.len())
+                .fold(a[i][0].clone() * b[0][j].clone(), |acc, k| acc + a[i][k].clone() * b[k][j].clone()))
+            .collect_vec())
+        .collect_vec()
+}
+
+fn matrix_pow<T: Clone + Add<Output=T> + Mul<Output=T>>(matrix: &Matrix<T>, mut exponent: u64, identity: Matrix<T>) -> Matrix<T> {
+    let mut result = identity;
+    let mut base = matrix.clone();
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = matrix_mul(&result, &base);
+        }
+        base = matrix_mul(&base, &base);
+        exponent >>= 1;
+    }
+    result
+}
+
+fn population<T: Clone + Add<Output=T> + Mul<Output=T>>(numbers: &Vec<i32>, days: u64, lift: impl Fn(u64) -> T) -> Result<T, String> {
+    if let Some(timer) = numbers.iter().find(|&&number| !(0..9).contains(&number)) {
+        return Err(format!("timer {} is outside 0..=8", timer));
+    }
+    let transition = (0..9)
+        .map(|to| (0..9)
+            .map(|from| lift(((from == to + 1) | ((from == 0) & ((to == 6) | (to == 8)))) as u64))
+            .collect_vec())
+        .collect_vec();
+    let identity = (0..9)
+        .map(|i| (0..9).map(|j| lift((i == j) as u64)).collect_vec())
+        .collect_vec();
+    let initial = (0..9)
+        .map(|timer| vec![lift(numbers.iter().filter(|&&number| number == timer).count() as u64)])
+        .collect_vec();
+    Ok(matrix_mul(&matrix_pow(&transition, days, identity), &initial)
+        .into_iter()
+        .map(|row| row[0].clone())
+        .reduce(|acc, count| acc + count)
+        .unwrap())
+}
+
+fn population_exact(numbers: &Vec<i32>, days: u64) -> Result<BigUint, String> {
+    population(numbers, days, BigUint::new)
+}
+
+fn population_modulo(numbers: &Vec<i32>, days: u64, modulus: u64) -> Result<u64, String> {
+    population(numbers, days, |value| Modular { value: value % modulus, modulus }).map(|count| count.value)
+}
+
+fn parse_days(arg: &str) -> Result<u64, String> {
+    arg.parse::<u64>().map_err(|_| format!("invalid day count: {}", arg))
+}
+
+fn parse_modulus(arg: &str) -> Result<u64, String> {
+    match arg.parse::<u64>() {
+        Ok(0) => Err("modulus must be positive".to_string()),
+        Ok(modulus) => Ok(modulus),
+        Err(_) => Err(format!("invalid modulus: {}", arg)),
     }
-    return fish.iter().map(|(_, count)| count).sum();
 }
 
 pub(crate) fn solve() {
@@ -38,4 +293,34 @@ pub(crate) fn solve() {
         .collect_vec();
     println!("{}", part1(&numbers, 80));
     println!("{}", part1(&numbers, 256));
+    let args = std::env::args().collect_vec();
+    let days = args.iter().find_map(|arg| arg.strip_prefix("--days=")).map(parse_days).transpose();
+    let modulus = args.iter().find_map(|arg| arg.strip_prefix("--modulo=")).map(parse_modulus).transpose();
+    let (days, modulus) = match (days, modulus) {
+        (Ok(days), Ok(modulus)) => (days, modulus),
+        (Err(error), _) | (_, Err(error)) => {
+            eprintln!("error: {}", error);
+            return;
+        }
+    };
+    if let Some(days) = days {
+        let count = match modulus {
+            Some(modulus) => population_modulo(&numbers, days, modulus).map(|count| count.to_string()),
+            None => population_exact(&numbers, days).map(|count| count.to_string()),
+        };
+        match count {
+            Ok(count) => println!("{}", count),
+            Err(error) => eprintln!("error: {}", error),
+        }
+    }
+    if let Some(path) = args.iter().find_map(|arg| arg.strip_prefix("--csv=")) {
+        let model = match args.iter().find_map(|arg| arg.strip_prefix("--model=")) {
+            Some(config) => Model::new(fs::read_to_string(config).unwrap().as_str()),
+            None => Model { species: vec![Species::lanternfish(&numbers)] }
+        };
+        match model.to_csv(days.unwrap_or(256)) {
+            Ok(csv) => fs::write(path, csv).unwrap(),
+            Err(error) => eprintln!("error: {}", error),
+        }
+    }
 }
@@ -2,17 +2,15 @@ use std::fs;
 use itertools::Itertools;
 
 fn bf_align_crabs(numbers: &Vec<i32>, distance: fn(&Vec<i32>, i32) -> i32) -> i32 {
-    let &max = numbers.iter().max().unwrap();
-    (0..max)
+    let (&min, &max) = numbers.iter().minmax().into_option().unwrap();
+    (min..max + 1)
         .map(|i| distance(&numbers, i))
         .min()
         .unwrap()
 }
 
 fn distances1(numbers: &Vec<i32>, target: i32) -> i32 {
-    numbers.iter()
-        .map(|&number| (number - target).abs())
-        .sum()
+    total_cost(numbers, target, &linear) as i32
 }
 
 fn part1(numbers: &Vec<i32>) -> i32 {
@@ -26,15 +24,8 @@ fn part1_faster(numbers: &Vec<i32>) -> i32 {
     distances1(numbers, median)
 }
 
-fn sequence_sum(n: i32) -> i32 {
-    (n + n * n) / 2
-}
-
 fn distances2(numbers: &Vec<i32>, target: i32) -> i32 {
-    numbers.iter()
-        .map(|&number| (number - target).abs())
-        .map(|n| sequence_sum(n))
-        .sum()
+    total_cost(numbers, target, &triangular) as i32
 }
 
 fn part2(numbers: &Vec<i32>) -> i32 {
@@ -42,11 +33,96 @@ fn part2(numbers: &Vec<i32>) -> i32 {
 }
 
 fn part2_faster(numbers: &Vec<i32>) -> i32 {
-    let avg: i32 = numbers.iter().sum::<i32>() / numbers.len() as i32;
-    (avg..avg + 1)
-        .map(|i| distances2(&numbers, i))
-        .min()
-        .unwrap()
+    optimize_alignment(numbers, triangular).1 as i32
+}
+
+fn linear(distance: i64) -> i64 {
+    distance
+}
+
+fn triangular(distance: i64) -> i64 {
+    (distance + distance * distance) / 2
+}
+
+fn quadratic(distance: i64) -> i64 {
+    distance * distance
+}
+
+fn total_cost(numbers: &Vec<i32>, target: i32, cost: &impl Fn(i64) -> i64) -> i64 {
+    weighted_total_cost(numbers, &vec![1; numbers.len()], target, cost)
+}
+
+fn weighted_total_cost(numbers: &Vec<i32>, weights: &Vec<i64>, target: i32, cost: &impl Fn(i64) -> i64) -> i64 {
+    numbers.iter()
+        .zip(weights.iter())
+        .map(|(&number, &weight)| weight * cost((number as i64 - target as i64).abs()))
+        .sum()
+}
+
+fn optimize_alignment(numbers: &Vec<i32>, cost: impl Fn(i64) -> i64) -> (i32, i64) {
+    optimize_weighted_alignment(numbers, &vec![1; numbers.len()], &cost)
+}
+
+fn optimize_weighted_alignment(numbers: &Vec<i32>, weights: &Vec<i64>, cost: &impl Fn(i64) -> i64) -> (i32, i64) {
+    let (&min, &max) = numbers.iter().minmax().into_option().unwrap();
+    let (mut low, mut high) = (min, max);
+    while low < high {
+        let mid = low + (high - low) / 2;
+        if weighted_total_cost(numbers, weights, mid, cost) <= weighted_total_cost(numbers, weights, mid + 1, cost) {
+            high = mid;
+        } else {
+            low = mid + 1;
+        }
+    }
+    (low, weighted_total_cost(numbers, weights, low, cost))
+}
+
+struct Crab {
+    position: Vec<i32>,
+    weight: i64,
+}
+
+impl Crab {
+    fn new(line: &str) -> Crab {
+        let (position, weight) = match line.split_once("@") {
+            Some((position, weight)) => (position, weight.trim().parse().unwrap()),
+            None => (line, 1)
+        };
+        Crab {
+            position: position.trim().split(",").map(|x| x.parse().unwrap()).collect_vec(),
+            weight,
+        }
+    }
+
+    fn fuel(&self, target: &Vec<i32>, cost: &impl Fn(i64) -> i64) -> i64 {
+        self.position.iter()
+            .zip(target.iter())
+            .map(|(&coordinate, &target)| self.weight * cost((coordinate as i64 - target as i64).abs()))
+            .sum()
+    }
+}
+
+struct Alignment {
+    target: Vec<i32>,
+    fuel: Vec<i64>,
+}
+
+impl Alignment {
+    fn total(&self) -> i64 {
+        self.fuel.iter().sum()
+    }
+}
+
+fn align_crabs(crabs: &Vec<Crab>, cost: impl Fn(i64) -> i64) -> Alignment {
+    let weights = crabs.iter().map(|crab| crab.weight).collect_vec();
+    let target = (0..crabs[0].position.len())
+        .map(|dimension| crabs.iter().map(|crab| crab.position[dimension]).collect_vec())
+        .map(|coordinates| optimize_weighted_alignment(&coordinates, &weights, &cost).0)
+        .collect_vec();
+    let fuel = crabs.iter()
+        .map(|crab| crab.fuel(&target, &cost))
+        .collect_vec();
+    Alignment { target, fuel }
 }
 
 pub(crate) fn solve() {
@@ -59,4 +135,58 @@ pub(crate) fn solve() {
     println!("{}", part1_faster(&numbers));
     println!("{}", part2(&numbers));
     println!("{}", part2_faster(&numbers));
+    for (name, cost) in [("linear", linear as fn(i64) -> i64), ("triangular", triangular), ("quadratic", quadratic)] {
+        let (position, fuel) = optimize_alignment(&numbers, cost);
+        println!("{}: position {}, cost {}", name, position, fuel);
+    }
+    if let Some(path) = std::env::args().find_map(|arg| arg.strip_prefix("--crabs=").map(|path| path.to_string())) {
+        let crabs = fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(|line| Crab::new(line))
+            .collect_vec();
+        let alignment = align_crabs(&crabs, triangular);
+        println!("target {:?}, cost {}", alignment.target, alignment.total());
+        for (crab, fuel) in crabs.iter().zip(alignment.fuel.iter()) {
+            println!("{:?} x{}: {}", crab.position, crab.weight, fuel);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bf_optimize_alignment(numbers: &Vec<i32>, cost: impl Fn(i64) -> i64) -> (i32, i64) {
+        let (&min, &max) = numbers.iter().minmax().into_option().unwrap();
+        (min..max + 1)
+            .map(|target| (target, total_cost(numbers, target, &cost)))
+            .min_by_key(|&(_, cost)| cost)
+            .unwrap()
+    }
+
+    #[test]
+    fn optimizer_matches_brute_force_on_example() {
+        let numbers = vec![16, 1, 2, 0, 4, 2, 7, 1, 2, 14];
+        assert_eq!(optimize_alignment(&numbers, linear), (2, 37));
+        assert_eq!(optimize_alignment(&numbers, triangular), (5, 168));
+        for cost in [linear as fn(i64) -> i64, triangular, quadratic] {
+            assert_eq!(optimize_alignment(&numbers, cost).1, bf_optimize_alignment(&numbers, cost).1);
+        }
+    }
+
+    #[test]
+    fn optimizer_matches_brute_force_on_random_inputs() {
+        let mut seed: u64 = 2021;
+        let mut next = |modulo: u64| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (seed >> 33) % modulo
+        };
+        for _ in 0..500 {
+            let numbers = (0..1 + next(20)).map(|_| next(100) as i32 - 50).collect_vec();
+            for cost in [linear as fn(i64) -> i64, triangular, quadratic, |d: i64| d * d * d] {
+                assert_eq!(optimize_alignment(&numbers, cost).1, bf_optimize_alignment(&numbers, cost).1);
+            }
+        }
+    }
 }
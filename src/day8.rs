@@ -1,29 +1,172 @@
 use std::fs;
 use itertools::Itertools;
 use std::collections::HashMap;
+use std::fmt;
+use std::time::Instant;
 
 struct InputSet {
     numbers: Vec<String>,
     outputs: Vec<String>,
 }
 
+const DIGIT_SEGMENTS: [&str; 10] = ["abcefg", "cf", "acdeg", "acdfg", "bcdf", "abdfg", "abdefg", "acf", "abcdefg", "abcdfg"];
+
 fn is_valid_digit(v: &str) -> bool {
-    ["abcefg", "cf", "acdeg", "acdfg", "bcdf", "abdfg", "abdefg", "acf", "abcdefg", "abcdfg"].contains(&v)
+    DIGIT_SEGMENTS.contains(&v)
 }
 
 fn decode_digit(v: &str) -> i32 {
-    match v {
-        "abcefg" => 0,
-        "cf" => 1,
-        "acdeg" => 2,
-        "acdfg" => 3,
-        "bcdf" => 4,
-        "abdfg" => 5,
-        "abdefg" => 6,
-        "acf" => 7,
-        "abcdefg" => 8,
-        "abcdfg" => 9,
-        _ => panic!("AAAAAA")
+    DIGIT_SEGMENTS.iter()
+        .position(|&digit| digit == v)
+        .expect("not a digit") as i32
+}
+
+#[derive(Debug, PartialEq)]
+enum DecodeError {
+    Ambiguous,
+    Unsatisfiable,
+    InvalidSegment,
+}
+
+struct DisplayLayout {
+    segments: Vec<char>,
+    symbols: Vec<(String, u32)>,
+}
+
+impl DisplayLayout {
+    fn seven_segment() -> DisplayLayout {
+        let definition = std::iter::once("abcdefg".to_string())
+            .chain(DIGIT_SEGMENTS.iter()
+                .enumerate()
+                .map(|(digit, pattern)| format!("{} {}", digit, pattern)))
+            .join("\n");
+        DisplayLayout::new(definition.as_str()).unwrap()
+    }
+
+    fn new(definition: &str) -> Result<DisplayLayout, String> {
+        let mut lines = definition.lines().filter(|line| !line.trim().is_empty());
+        let segments = lines.next().ok_or("layout has no segment line")?.trim().chars().collect_vec();
+        if segments.len() > 32 {
+            return Err(format!("at most 32 segments are supported, got {}", segments.len()));
+        }
+        if segments.iter().duplicates().next().is_some() {
+            return Err(format!("duplicate segment in {}", segments.iter().collect::<String>()));
+        }
+        let mut layout = DisplayLayout { segments, symbols: vec![] };
+        layout.symbols = lines
+            .map(|line| match line.split_whitespace().collect_vec()[..] {
+                [symbol, pattern] => layout.to_mask(pattern)
+                    .map(|mask| (symbol.to_string(), mask))
+                    .ok_or_else(|| format!("unknown segment in pattern {}", pattern)),
+                _ => Err(format!("expected a symbol and a pattern, got {:?}", line)),
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(layout)
+    }
+
+    fn to_mask(&self, pattern: &str) -> Option<u32> {
+        pattern.chars()
+            .map(|c| self.segments.iter().position(|&segment| segment == c))
+            .try_fold(0, |acc, segment| Some(acc | 1 << segment?))
+    }
+
+    fn all_segments(&self) -> u32 {
+        u32::MAX.checked_shr(32 - self.segments.len() as u32).unwrap_or(0)
+    }
+}
+
+fn constrain(candidates: &Vec<u32>, pattern: u32, glyph: u32) -> Option<Vec<u32>> {
+    let constrained = candidates.iter()
+        .enumerate()
+        .map(|(wire, &candidate)| if pattern & 1 << wire != 0 { candidate & glyph } else { candidate & !glyph })
+        .collect_vec();
+    Some(constrained).filter(|constrained| constrained.iter().all(|&candidate| candidate != 0))
+}
+
+fn find_matching(candidates: &[u32], used: u32) -> Option<Vec<usize>> {
+    match candidates.split_first() {
+        None => Some(vec![]),
+        Some((&first, rest)) => (0..32)
+            .filter(|segment| first & !used & 1 << segment != 0)
+            .find_map(|segment| find_matching(rest, used | 1 << segment)
+                .map(|matching| std::iter::once(segment).chain(matching).collect_vec()))
+    }
+}
+
+struct Solution {
+    glyphs: Vec<usize>,
+    wiring: Vec<usize>,
+}
+
+fn deduce(patterns: &Vec<u32>, glyphs: &Vec<u32>, outputs: &Vec<usize>, candidates: Vec<u32>, assigned: &mut Vec<Option<usize>>, solutions: &mut Vec<Solution>) {
+    let options = |index: usize| -> Vec<(usize, Vec<u32>)> {
+        (0..glyphs.len())
+            .filter(|glyph| !assigned.contains(&Some(*glyph)))
+            .filter(|&glyph| glyphs[glyph].count_ones() == patterns[index].count_ones())
+            .filter_map(|glyph| constrain(&candidates, patterns[index], glyphs[glyph]).map(|constrained| (glyph, constrained)))
+            .collect_vec()
+    };
+    let next = (0..patterns.len())
+        .filter(|&index| assigned[index].is_none())
+        .map(|index| (index, options(index)))
+        .min_by_key(|(_, options)| options.len());
+    match next {
+        None => {
+            let glyphs = assigned.iter().map(|glyph| glyph.unwrap()).collect_vec();
+            if solutions.iter().any(|solution| outputs.iter().all(|&output| solution.glyphs[output] == glyphs[output])) {
+                return;
+            }
+            if let Some(wiring) = find_matching(&candidates, 0) {
+                solutions.push(Solution { glyphs, wiring });
+            }
+        }
+        Some((index, options)) => {
+            for (glyph, constrained) in options {
+                assigned[index] = Some(glyph);
+                deduce(patterns, glyphs, outputs, constrained, assigned, solutions);
+                assigned[index] = None;
+                if solutions.len() > 1 {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+struct WiringReport {
+    wiring: Vec<(char, char)>,
+    signals: Vec<(String, String)>,
+    outputs: Vec<(String, u32)>,
+}
+
+impl WiringReport {
+    fn output(&self) -> String {
+        self.outputs.iter().map(|(symbol, _)| symbol.as_str()).collect()
+    }
+
+    fn render_seven_segment(&self) -> String {
+        let rows = [[None, Some(0), None], [Some(1), Some(3), Some(2)], [Some(4), Some(6), Some(5)]];
+        rows.iter()
+            .map(|row| self.outputs.iter()
+                .map(|(_, glyph)| row.iter()
+                    .enumerate()
+                    .map(|(column, segment)| match segment {
+                        Some(index) if glyph & 1 << index != 0 => if column == 1 { '_' } else { '|' },
+                        _ => ' '
+                    })
+                    .collect::<String>())
+                .join(" "))
+            .join("\n")
+    }
+}
+
+impl fmt::Display for WiringReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "wiring: {}", self.wiring.iter().map(|(wire, segment)| format!("{}->{}", wire, segment)).join(" "))?;
+        for (pattern, symbol) in self.signals.iter() {
+            writeln!(f, "  {:<8} {}", pattern, symbol)?;
+        }
+        write!(f, "output: {}", self.output())
     }
 }
 
@@ -73,6 +216,47 @@ impl InputSet {
             .fold(0, |acc, digit| acc * 10 + digit)
     }
 
+    fn deduce_wiring(&self, layout: &DisplayLayout) -> Result<WiringReport, DecodeError> {
+        let glyphs = layout.symbols.iter().map(|(_, glyph)| *glyph).collect_vec();
+        let to_masks = |patterns: &Vec<String>| patterns.iter()
+            .map(|pattern| layout.to_mask(pattern))
+            .collect::<Option<Vec<u32>>>()
+            .ok_or(DecodeError::InvalidSegment);
+        let numbers = to_masks(&self.numbers)?;
+        let outputs = to_masks(&self.outputs)?;
+        let patterns = numbers.iter().chain(outputs.iter()).cloned().unique().collect_vec();
+        let index_of = |mask: &u32| patterns.iter().position(|pattern| pattern == mask).unwrap();
+        let mut solutions = vec![];
+        let candidates = vec![layout.all_segments(); layout.segments.len()];
+        deduce(&patterns, &glyphs, &outputs.iter().map(index_of).collect_vec(), candidates, &mut vec![None; patterns.len()], &mut solutions);
+        let symbol_of = |solution: &Solution, mask: &u32| solution.glyphs[index_of(mask)];
+        let reports = solutions.iter()
+            .map(|solution| WiringReport {
+                wiring: solution.wiring.iter()
+                    .enumerate()
+                    .map(|(wire, &segment)| (layout.segments[wire], layout.segments[segment]))
+                    .collect_vec(),
+                signals: self.numbers.iter()
+                    .zip(numbers.iter())
+                    .map(|(pattern, mask)| (pattern.clone(), layout.symbols[symbol_of(solution, mask)].0.clone()))
+                    .collect_vec(),
+                outputs: outputs.iter()
+                    .map(|mask| layout.symbols[symbol_of(solution, mask)].clone())
+                    .collect_vec(),
+            })
+            .unique_by(|report| report.output())
+            .collect_vec();
+        match reports.len() {
+            0 => Err(DecodeError::Unsatisfiable),
+            1 => Ok(reports.into_iter().next().unwrap()),
+            _ => Err(DecodeError::Ambiguous),
+        }
+    }
+
+    fn deduce_output(&self, layout: &DisplayLayout) -> Result<String, DecodeError> {
+        self.deduce_wiring(layout).map(|report| report.output())
+    }
+
     fn decode_output(&self) -> i32 {
         "abcdefg".chars()
             .permutations(7)
@@ -96,6 +280,16 @@ fn part2(data: &Vec<InputSet>) -> i32 {
         .sum()
 }
 
+fn part2_faster(data: &Vec<InputSet>) -> Result<i32, (usize, DecodeError)> {
+    let layout = DisplayLayout::seven_segment();
+    data.iter()
+        .enumerate()
+        .map(|(index, x)| x.deduce_output(&layout)
+            .map(|value| value.parse::<i32>().unwrap())
+            .map_err(|error| (index + 1, error)))
+        .sum()
+}
+
 pub(crate) fn solve() {
     let inputs = fs::read_to_string("8.txt")
         .unwrap()
@@ -103,6 +297,41 @@ pub(crate) fn solve() {
         .map(|line| InputSet::new(line))
         .collect_vec();
     println!("{}", part1(&inputs));
+    let start = Instant::now();
     println!("{}", part2(&inputs));
+    println!("brute force: {:?}", start.elapsed());
+    let start = Instant::now();
+    match part2_faster(&inputs) {
+        Ok(value) => println!("{}", value),
+        Err((entry, error)) => println!("entry {}: {:?}", entry, error),
+    }
+    println!("deduction: {:?}", start.elapsed());
+    let args = std::env::args().collect_vec();
+    if args.iter().any(|arg| arg == "--verbose") {
+        let layout = DisplayLayout::seven_segment();
+        for (index, entry) in inputs.iter().enumerate() {
+            match entry.deduce_wiring(&layout) {
+                Ok(report) => println!("entry {}\n{}\n{}", index + 1, report, report.render_seven_segment()),
+                Err(error) => println!("entry {}: {:?}", index + 1, error),
+            }
+        }
+    }
+    if let Some(path) = args.iter().find_map(|arg| arg.strip_prefix("--display=")) {
+        let layout = fs::read_to_string(path)
+            .map_err(|error| error.to_string())
+            .and_then(|definition| DisplayLayout::new(definition.as_str()));
+        match layout {
+            Ok(layout) => {
+                let entries = args.iter().find_map(|arg| arg.strip_prefix("--entries=")).unwrap_or("8.txt");
+                for (index, entry) in fs::read_to_string(entries).unwrap().lines().enumerate() {
+                    match InputSet::new(entry).deduce_output(&layout) {
+                        Ok(value) => println!("{}", value),
+                        Err(error) => println!("entry {}: {:?}", index + 1, error),
+                    }
+                }
+            }
+            Err(error) => eprintln!("error: {}: {}", path, error),
+        }
+    }
 }
 
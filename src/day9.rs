@@ -1,9 +1,92 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use itertools::Itertools;
 
+struct UnionFind {
+    parent: Vec<usize>,
+    size: Vec<usize>,
+}
+
+impl UnionFind {
+    fn new(n: usize) -> UnionFind {
+        UnionFind { parent: (0..n).collect_vec(), size: vec![1; n] }
+    }
+
+    fn find(&mut self, x: usize) -> usize {
+        let mut root = x;
+        while self.parent[root] != root {
+            root = self.parent[root];
+        }
+        let mut current = x;
+        while self.parent[current] != root {
+            let next = self.parent[current];
+            self.parent[current] = root;
+            current = next;
+        }
+        root
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.find(a), self.find(b));
+        if a == b {
+            return;
+        }
+        let (big, small) = if self.size[a] >= self.size[b] { (a, b) } else { (b, a) };
+        self.parent[small] = big;
+        self.size[big] += self.size[small];
+    }
+}
+
+struct Basin {
+    size: usize,
+    low_points: Vec<(usize, usize)>,
+    boundary: Vec<(usize, usize)>,
+}
+
+struct BasinMap {
+    labels: Vec<Vec<Option<usize>>>,
+    basins: Vec<Basin>,
+}
+
+impl BasinMap {
+    fn render(&self) -> String {
+        let symbols = ('a'..='z').chain('A'..='Z').collect_vec();
+        self.labels.iter()
+            .map(|row| row.iter()
+                .map(|label| label.map_or('#', |basin| symbols[basin % symbols.len()]))
+                .collect::<String>())
+            .join("\n")
+    }
+}
+
+struct Watershed {
+    drains: Vec<Vec<Vec<(usize, usize)>>>,
+}
+
+impl Watershed {
+    fn basin_sizes(&self) -> HashMap<(usize, usize), usize> {
+        self.drains.iter()
+            .flat_map(|row| row.iter())
+            .filter(|sinks| sinks.len() == 1)
+            .map(|sinks| sinks[0])
+            .counts()
+    }
+
+    fn contested(&self) -> Vec<((usize, usize), Vec<(usize, usize)>)> {
+        self.drains.iter()
+            .enumerate()
+            .flat_map(|(x, row)| row.iter()
+                .enumerate()
+                .filter(|(_, sinks)| sinks.len() > 1)
+                .map(move |(y, sinks)| ((x, y), sinks.clone())))
+            .collect_vec()
+    }
+}
+
 struct Board {
     array: Vec<Vec<i32>>,
+    barrier: i32,
+    diagonal: bool,
 }
 
 impl Board {
@@ -23,6 +106,10 @@ impl Board {
         self.get_value(x, y) + 1
     }
 
+    fn is_barrier(&self, x: &usize, y: &usize) -> bool {
+        self.get_value(x, y) >= self.barrier
+    }
+
     fn is_valid_index(&self, x: &i32, y: &i32) -> bool {
         (*x >= 0) & (*x < self.height() as i32) & (*y >= 0) & (*y < self.width() as i32)
     }
@@ -30,7 +117,9 @@ impl Board {
     fn get_neighbours(&self, x: &usize, y: &usize) -> Vec<(usize, usize)> {
         let x = *x as i32;
         let y = *y as i32;
-        let potential = [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)];
+        let straight = [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)];
+        let diagonal = [(x - 1, y - 1), (x - 1, y + 1), (x + 1, y - 1), (x + 1, y + 1)];
+        let potential = if self.diagonal { [&straight[..], &diagonal[..]].concat() } else { straight.to_vec() };
         potential.iter()
             .filter(|(x, y)| self.is_valid_index(x, y))
             .map(|(x, y)| (*x as usize, *y as usize))
@@ -52,6 +141,39 @@ impl Board {
             .collect_vec()
     }
 
+    fn get_plateaus(&self) -> Vec<Vec<(usize, usize)>> {
+        let mut visited: HashSet<(usize, usize)> = HashSet::new();
+        let mut plateaus = vec![];
+        for (i, j) in (0..self.height()).flat_map(|i| (0..self.width()).map(move |j| (i, j))) {
+            if !visited.insert((i, j)) {
+                continue;
+            }
+            let height = self.get_value(&i, &j);
+            let mut area = vec![];
+            let mut to_check = vec![(i, j)];
+            while let Some((x, y)) = to_check.pop() {
+                area.push((x, y));
+                for (nx, ny) in self.get_neighbours(&x, &y) {
+                    if (self.get_value(&nx, &ny) == height) && visited.insert((nx, ny)) {
+                        to_check.push((nx, ny));
+                    }
+                }
+            }
+            plateaus.push(area.into_iter().sorted().collect_vec());
+        }
+        plateaus
+    }
+
+    fn get_low_areas(&self) -> Vec<Vec<(usize, usize)>> {
+        self.get_plateaus()
+            .into_iter()
+            .filter(|area| area.iter()
+                .all(|(x, y)| self.get_neighbours(x, y)
+                    .iter()
+                    .all(|(nx, ny)| self.get_value(nx, ny) >= self.get_value(x, y))))
+            .collect_vec()
+    }
+
     fn get_basin_for_low(&self, start_x: &usize, start_y: &usize) -> HashSet<(usize, usize)> {
         let mut basin = HashSet::new();
         let mut to_check = vec![(*start_x, *start_y)];
@@ -61,11 +183,93 @@ impl Board {
             self.get_neighbours(&x, &y)
                 .iter()
                 .filter(|(nx, ny)| !basin.contains(&(*nx, *ny)))
-                .filter(|(nx, ny)| self.get_value(nx, ny) != 9)
+                .filter(|(nx, ny)| !self.is_barrier(nx, ny))
                 .for_each(|(nx, ny)| to_check.push((*nx, *ny)))
         }
         basin
     }
+
+    fn label_basins(&self) -> BasinMap {
+        let index = |x: usize, y: usize| x * self.width() + y;
+        let is_basin = |x: usize, y: usize| !self.is_barrier(&x, &y);
+        let mut union_find = UnionFind::new(self.height() * self.width());
+        for x in 0..self.height() {
+            for y in 0..self.width() {
+                if !is_basin(x, y) {
+                    continue;
+                }
+                self.get_neighbours(&x, &y)
+                    .into_iter()
+                    .filter(|&(nx, ny)| is_basin(nx, ny))
+                    .for_each(|(nx, ny)| union_find.union(index(x, y), index(nx, ny)));
+            }
+        }
+        let mut roots: Vec<usize> = vec![];
+        let mut basin_ids: HashMap<usize, usize> = HashMap::new();
+        let labels = (0..self.height())
+            .map(|x| (0..self.width())
+                .map(|y| if is_basin(x, y) {
+                    let root = union_find.find(index(x, y));
+                    Some(*basin_ids.entry(root).or_insert_with(|| {
+                        roots.push(root);
+                        roots.len() - 1
+                    }))
+                } else {
+                    None
+                })
+                .collect_vec())
+            .collect_vec();
+        let mut basins = roots.iter()
+            .map(|&root| Basin { size: union_find.size[root], low_points: vec![], boundary: vec![] })
+            .collect_vec();
+        for (x, y) in self.get_low_points() {
+            if let Some(basin) = labels[x][y] {
+                basins[basin].low_points.push((x, y));
+            }
+        }
+        for x in 0..self.height() {
+            for y in 0..self.width() {
+                if labels[x][y].is_some() {
+                    continue;
+                }
+                self.get_neighbours(&x, &y)
+                    .iter()
+                    .filter_map(|(nx, ny)| labels[*nx][*ny])
+                    .unique()
+                    .for_each(|basin| basins[basin].boundary.push((x, y)));
+            }
+        }
+        BasinMap { labels, basins }
+    }
+
+    fn watershed(&self) -> Watershed {
+        let mut drains = vec![vec![vec![]; self.width()]; self.height()];
+        let plateaus = self.get_plateaus()
+            .into_iter()
+            .filter(|area| !self.is_barrier(&area[0].0, &area[0].1))
+            .sorted_by_key(|area| self.get_value(&area[0].0, &area[0].1))
+            .collect_vec();
+        for area in plateaus {
+            let height = self.get_value(&area[0].0, &area[0].1);
+            let lower = area.iter()
+                .flat_map(|(x, y)| self.get_neighbours(x, y))
+                .filter(|(nx, ny)| self.get_value(nx, ny) < height)
+                .collect_vec();
+            let sinks = match lower.iter().map(|(nx, ny)| self.get_value(nx, ny)).min() {
+                None => vec![area[0]],
+                Some(lowest) => lower.iter()
+                    .filter(|(nx, ny)| self.get_value(nx, ny) == lowest)
+                    .flat_map(|(nx, ny)| drains[*nx][*ny].clone())
+                    .unique()
+                    .sorted()
+                    .collect_vec()
+            };
+            for (x, y) in area {
+                drains[x][y] = sinks.clone();
+            }
+        }
+        Watershed { drains }
+    }
 }
 
 fn part1(board: &Board) -> i32 {
@@ -75,6 +279,13 @@ fn part1(board: &Board) -> i32 {
         .sum()
 }
 
+fn part1_plateaus(board: &Board) -> i32 {
+    board.get_low_areas()
+        .iter()
+        .map(|area| board.get_risk_level(&area[0].0, &area[0].1))
+        .sum()
+}
+
 fn part2(board: &Board) -> i32 {
     let basin_sizes = board.get_low_points()
         .iter()
@@ -85,13 +296,47 @@ fn part2(board: &Board) -> i32 {
     basin_sizes[0..3].iter().fold(1, |acc, x| acc * x)
 }
 
+fn part2_faster(board: &Board) -> i32 {
+    board.label_basins()
+        .basins
+        .iter()
+        .map(|basin| basin.size as i32)
+        .sorted_by(|a, b| b.cmp(a))
+        .take(3)
+        .product()
+}
+
 pub(crate) fn solve() {
     let inputs = fs::read_to_string("9.txt")
         .unwrap()
         .lines()
         .map(|line| line.chars().map(|c| c.to_string().parse::<i32>().unwrap()).collect_vec())
         .collect_vec();
-    let board = Board { array: inputs };
+    let args = std::env::args().collect_vec();
+    let barrier = args.iter()
+        .find_map(|arg| arg.strip_prefix("--barrier="))
+        .map_or(9, |barrier| barrier.parse().unwrap());
+    let diagonal = args.iter().any(|arg| arg == "--diagonal");
+    let board = Board { array: inputs, barrier, diagonal };
     println!("{}", part1(&board));
     println!("{}", part2(&board));
+    println!("{}", part2_faster(&board));
+    if std::env::args().any(|arg| arg == "--verbose") {
+        println!("{}", board.label_basins().render());
+    }
+    if args.iter().any(|arg| arg == "--plateaus") {
+        for area in board.get_low_areas() {
+            println!("low area {:?}: risk {}", area, board.get_risk_level(&area[0].0, &area[0].1));
+        }
+        println!("{}", part1_plateaus(&board));
+    }
+    if args.iter().any(|arg| arg == "--watershed") {
+        let watershed = board.watershed();
+        for ((x, y), size) in watershed.basin_sizes().iter().sorted() {
+            println!("low point {},{}: {} cells", x, y, size);
+        }
+        for ((x, y), sinks) in watershed.contested() {
+            println!("cell {},{} drains to {:?}", x, y, sinks);
+        }
+    }
 }